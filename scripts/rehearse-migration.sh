@@ -0,0 +1,58 @@
+#!/usr/bin/env bash
+# Rehearses the push-based migration of one project end to end on a local
+# starknet-devnet: deploys the v3 contract, builds and submits the batches,
+# then verifies the balances against `tonnes/<project>.json`.
+#
+# Usage: scripts/rehearse-migration.sh <project> <v3 sierra class> <v3 casm class> [constructor args...]
+#
+# Needs `starknet-devnet` and `starkli` on the PATH. Constructor arguments are
+# passed to `starkli deploy` as is, `ACCOUNT` expands to the deployer address
+# (predeployed account 0 of `--seed 0`, which also submits the batches).
+set -euo pipefail
+
+if [ $# -lt 3 ]; then
+    sed -n 6p "$0" | sed 's/^# //'
+    exit 1
+fi
+PROJECT=$1
+SIERRA=$2
+CASM=$3
+shift 3
+
+PORT=${DEVNET_PORT:-5050}
+RPC=http://127.0.0.1:$PORT
+ACCOUNT=0x064b48806902a367c8598f4f95c305e8c1a1acba5f082d294a43793113115691
+PRIVATE_KEY=0x0000000000000000000000000000000071d7bb07b9a64f6f78ac4c816aff4da9
+WORKDIR=$(mktemp -d)
+
+starknet-devnet --seed 0 --port "$PORT" >"$WORKDIR/devnet.log" 2>&1 &
+DEVNET=$!
+trap 'kill $DEVNET; rm -rf "$WORKDIR"' EXIT
+until curl -sf "$RPC/is_alive" >/dev/null; do
+    sleep 1
+done
+echo "Devnet running on $RPC"
+
+export STARKNET_RPC=$RPC STARKNET_PRIVATE_KEY=$PRIVATE_KEY
+starkli account fetch "$ACCOUNT" --output "$WORKDIR/account.json"
+export STARKNET_ACCOUNT=$WORKDIR/account.json
+
+CLASS_HASH=$(starkli declare "$SIERRA" --casm-file "$CASM" 2>/dev/null | tail -n 1)
+ARGS=()
+for arg in "$@"; do
+    ARGS+=("${arg//ACCOUNT/$ACCOUNT}")
+done
+CONTRACT=$(starkli deploy "$CLASS_HASH" "${ARGS[@]}" 2>/dev/null | tail -n 1)
+echo "v3 contract deployed at $CONTRACT"
+
+cargo run --bin batches -- --project "$PROJECT" --contract "$CONTRACT" --out "$WORKDIR/batches"
+PRIVATE_KEY=$PRIVATE_KEY cargo run --bin migrate -- \
+    --project "$PROJECT" --account "$ACCOUNT" --rpc "$RPC" --dir "$WORKDIR/batches"
+
+# Devnet has no multicall contract. It reports SN_SEPOLIA and restarts its block
+# numbers on every run, so its calls are cached in the rehearsal's own directory
+BLOCK=$(starkli block-number)
+CALL_STRATEGY=batch CALL_CACHE_DIR=$WORKDIR/cache cargo run --bin verify-migration -- \
+    --project "$PROJECT" --contract "$CONTRACT" --block "$BLOCK" --rpc "$RPC" \
+    --batches "$WORKDIR/batches"
+echo "Rehearsal of $PROJECT passed at block $BLOCK"
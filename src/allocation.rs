@@ -0,0 +1,49 @@
+use serde_json::Value;
+use starknet::core::types::Felt;
+use std::{collections::BTreeMap, fs::File};
+
+/// `tonnes/<project>.json` as owner → amount of tonnes (9 decimals), without zero amounts.
+pub fn load_allocation(project: &str) -> Result<BTreeMap<Felt, u128>, Box<dyn std::error::Error>> {
+    let file = File::open("tonnes/".to_owned() + project + ".json")?;
+    let data: Value = serde_json::from_reader(file)?;
+    let mut entries = vec![];
+    for (owner, amount) in data.as_object().ok_or("allocation should be an object")? {
+        let amount = amount
+            .as_u64()
+            .ok_or_else(|| format!("amount of {} should be an integer", owner))?;
+        entries.push((Felt::from_hex(owner)?, amount.into()));
+    }
+    Ok(merge_owners(entries))
+}
+
+/// Sums the amounts of owners listed more than once, e.g. with and without the
+/// leading zero of their address, which parse to the same `Felt`.
+pub fn merge_owners(entries: impl IntoIterator<Item = (Felt, u128)>) -> BTreeMap<Felt, u128> {
+    let mut owners = BTreeMap::new();
+    for (owner, amount) in entries {
+        if amount > 0 {
+            *owners.entry(owner).or_insert(0) += amount;
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_owners_sums_padded_and_unpadded_addresses() {
+        let padded =
+            Felt::from_hex("0x02ab6e4ff64d7f4a080c0a24e7815c1688099bd56191cd79c739aec30095f316");
+        let unpadded =
+            Felt::from_hex("0x2ab6e4ff64d7f4a080c0a24e7815c1688099bd56191cd79c739aec30095f316");
+        let owners = merge_owners([
+            (padded.unwrap(), 3639393939),
+            (unpadded.unwrap(), 36393939393),
+            (Felt::ONE, 0),
+        ]);
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners.values().sum::<u128>(), 3639393939 + 36393939393);
+    }
+}
@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use rust_playground::{
+    merkle::MerkleTree,
+    model::{self, normalize, read_json, Snapshot},
+};
+use serde_json::{json, Value};
+use starknet::core::types::Felt;
+use std::{collections::BTreeMap, sync::Arc};
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct TokenRecord {
+    project: String,
+    version: String,
+    slot: String,
+    owner: String,
+    value: String,
+    value_in_yielder: String,
+    value_in_offsetter: String,
+    account: String,
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    snapshot: Snapshot,
+    trees: BTreeMap<String, MerkleTree>,
+    tokens: BTreeMap<String, Vec<TokenRecord>>,
+}
+
+fn load() -> Result<Store, Box<dyn std::error::Error>> {
+    let snapshot = model::load()?;
+    let mut store = Store {
+        trees: snapshot
+            .allocations
+            .iter()
+            .map(|(project, allocation)| (project.clone(), MerkleTree::new(allocation)))
+            .collect(),
+        snapshot,
+        ..Default::default()
+    };
+
+    for version in ["v1", "v2"] {
+        let Some(data) = read_json("output/s".to_owned() + version + ".json")? else {
+            continue;
+        };
+        for (project, slots) in data.as_object().unwrap() {
+            for (slot, tokens) in slots.as_object().unwrap() {
+                for (token_id, info) in tokens.as_object().unwrap() {
+                    let field = |key: &str| info[key].as_str().unwrap_or_default().to_string();
+                    store
+                        .tokens
+                        .entry(token_id.clone())
+                        .or_default()
+                        .push(TokenRecord {
+                            project: project.clone(),
+                            version: version.to_string(),
+                            slot: slot.clone(),
+                            owner: field("owner"),
+                            value: field("value"),
+                            value_in_yielder: field("value_in_yielder"),
+                            value_in_offsetter: field("value_in_offsetter"),
+                            account: field("account"),
+                        });
+                }
+            }
+        }
+    }
+
+    Ok(store)
+}
+
+async fn snapshot(State(store): State<Arc<Store>>) -> Json<Value> {
+    Json(store.snapshot.metadata.clone())
+}
+
+async fn owner(
+    State(store): State<Arc<Store>>,
+    Path(address): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let address = normalize(&address).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let projects = store
+        .snapshot
+        .owners
+        .get(&address)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let owner = Felt::from_hex(&address).unwrap();
+    let mut breakdown = BTreeMap::new();
+    for (project, info) in projects {
+        let tree = &store.trees[project];
+        breakdown.insert(
+            project.clone(),
+            json!({
+                "tonnes": info.tonnes,
+                "snapshots": info.snapshots,
+                "merkle_root": tree.root().to_hex_string(),
+                "merkle_proof": tree.proof(&owner, info.tonnes).map(|proof| {
+                    proof.iter().map(|p| p.to_hex_string()).collect::<Vec<_>>()
+                }),
+            }),
+        );
+    }
+
+    Ok(Json(json!({ "owner": address, "projects": breakdown })))
+}
+
+async fn project_totals(
+    State(store): State<Arc<Store>>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let totals = store
+        .snapshot
+        .projects
+        .get(&name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(json!({
+        "project": name,
+        "totals": totals,
+        "merkle_root": store.trees[&name].root().to_hex_string(),
+    })))
+}
+
+async fn token(
+    State(store): State<Arc<Store>>,
+    Path(token_id): Path<String>,
+) -> Result<Json<Vec<TokenRecord>>, StatusCode> {
+    let tokens = store.tokens.get(&token_id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(tokens.clone()))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let addr = std::env::var("API_ADDR").unwrap_or("127.0.0.1:3000".to_string());
+
+    let store = load()?;
+    println!(
+        "Loaded {} owners across {} projects",
+        store.snapshot.owners.len(),
+        store.snapshot.projects.len()
+    );
+
+    let app = Router::new()
+        .route("/snapshot", get(snapshot))
+        .route("/owners/:address", get(owner))
+        .route("/projects/:name/totals", get(project_totals))
+        .route("/tokens/:token_id", get(token))
+        .with_state(Arc::new(store));
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    println!("Serving API on http://{}", addr);
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use clap::Parser;
+use rust_playground::{
+    allocation::load_allocation,
+    migration::{
+        batch_paths, execute_calldata_len, split, Batch, CallTemplate, Entrypoint, Recipient,
+    },
+};
+use starknet::core::types::Felt;
+use std::path::Path;
+
+/// Builds unsigned v3 migration batches from a tonnes allocation.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Project name, as in `tonnes/<project>.json`
+    #[arg(long)]
+    project: String,
+    /// v3 project contract receiving the calls
+    #[arg(long)]
+    contract: String,
+    /// v3 token id the allocation is minted or transferred in
+    #[arg(long, default_value = "1")]
+    token_id: u128,
+    #[arg(long, value_enum, default_value_t = Entrypoint::Mint)]
+    entrypoint: Entrypoint,
+    /// Holder of the tokens when using `transfer`
+    #[arg(long)]
+    from: Option<String>,
+    /// Maximum `__execute__` calldata length per transaction
+    #[arg(long, default_value_t = 3000)]
+    max_calldata: usize,
+    /// Maximum number of calls per transaction, as a proxy for the step budget
+    #[arg(long, default_value_t = 100)]
+    max_calls: usize,
+    #[arg(long, default_value = "batches")]
+    out: String,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let from = args.from.as_deref().map(Felt::from_hex).transpose()?;
+    let template = CallTemplate::new(
+        Felt::from_hex(&args.contract)?,
+        args.entrypoint,
+        args.token_id,
+        from,
+    )
+    .map_err(|_| "--from is required for transfer")?;
+
+    let dir = Path::new(&args.out).join(&args.project);
+    if dir.join("journal.json").exists() {
+        return Err(format!(
+            "{} has a journal, its batches were already submitted",
+            dir.display()
+        )
+        .into());
+    }
+    std::fs::create_dir_all(&dir)?;
+    // Batches of a previous build would be picked up by `migrate` and `verify-migration`
+    for path in batch_paths(&dir)? {
+        std::fs::remove_file(path)?;
+    }
+
+    // Sorted by owner, so reruns produce identical batches and checksums
+    let recipients: Vec<Recipient> = load_allocation(&args.project)?
+        .into_iter()
+        .map(|(owner, amount)| Recipient { owner, amount })
+        .collect();
+
+    let batches = split(recipients, &template, args.max_calls, args.max_calldata);
+
+    let mut total = 0;
+    for (index, recipients) in batches.into_iter().enumerate() {
+        let batch = Batch::new(&args.project, index, template, recipients);
+        println!(
+            "Batch {}: {} recipients, {} calldata felts, checksum {}",
+            index,
+            batch.recipients.len(),
+            execute_calldata_len(&batch.calls),
+            batch.checksum.to_hex_string()
+        );
+        total += batch.total_amount;
+
+        std::fs::write(
+            dir.join(format!("batch_{:04}.json", index)),
+            serde_json::to_string_pretty(&batch)?,
+        )?;
+    }
+
+    println!(
+        "Total amount batched for {} is {} tonnes",
+        args.project,
+        total / 1_000_000_000
+    );
+
+    Ok(())
+}
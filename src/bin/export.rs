@@ -0,0 +1,262 @@
+use arrow::{
+    array::{ArrayRef, BooleanArray, Decimal128Array, StringArray},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use parquet::arrow::ArrowWriter;
+use rust_playground::model::read_json;
+use serde_json::Value;
+use std::{error::Error, fs::File, path::Path, sync::Arc};
+
+/// USDC values are stored with 6 decimals, tonnes with 9.
+pub const VALUE_SCALE: i8 = 6;
+pub const TONNES_SCALE: i8 = 9;
+
+enum Column {
+    Text(Vec<String>),
+    Decimal(Vec<u128>, i8),
+    Bool(Vec<bool>),
+}
+
+struct Table {
+    name: &'static str,
+    columns: Vec<(&'static str, Column)>,
+}
+
+fn format_decimal(value: u128, scale: i8) -> String {
+    let unit = 10_u128.pow(scale as u32);
+    format!(
+        "{}.{:0width$}",
+        value / unit,
+        value % unit,
+        width = scale as usize
+    )
+}
+
+/// Amount written either as a decimal string or as a JSON number.
+fn parse_value(value: &Value, path: &Path) -> Result<u128, String> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        _ => value.as_u64().map(u128::from),
+    }
+    .ok_or(format!("{}: invalid amount {}", path.display(), value))
+}
+
+/// JSON of `path`, failing when it is missing or empty.
+fn read_required(path: &Path) -> Result<Value, Box<dyn Error>> {
+    Ok(read_json(path)?.ok_or(format!("{} is missing or empty", path.display()))?)
+}
+
+fn as_object<'a>(
+    value: &'a Value,
+    path: &Path,
+) -> Result<&'a serde_json::Map<String, Value>, String> {
+    value.as_object().ok_or(format!(
+        "{}: expected an object, got {}",
+        path.display(),
+        value
+    ))
+}
+
+/// JSON files of `dir`, in name order.
+fn json_paths(dir: &str) -> Result<Vec<std::path::PathBuf>, Box<dyn Error>> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir, e))? {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// One row per scanned token, from `output/sv*.json`.
+fn scan_table() -> Result<Table, Box<dyn Error>> {
+    let mut project = vec![];
+    let mut version = vec![];
+    let mut slot = vec![];
+    let mut token_id = vec![];
+    let mut owner = vec![];
+    let mut value = vec![];
+    let mut yielder = vec![];
+    let mut offsetter = vec![];
+    let mut account = vec![];
+
+    for v in ["v1", "v2"] {
+        let path = "output/s".to_owned() + v + ".json";
+        let path = Path::new(&path);
+        let json = read_required(path)?;
+        for (name, slots) in as_object(&json, path)? {
+            for (slot_id, tokens) in as_object(slots, path)? {
+                for (id, token_info) in as_object(tokens, path)? {
+                    project.push(name.clone());
+                    version.push(v.to_string());
+                    slot.push(slot_id.clone());
+                    token_id.push(id.clone());
+                    owner.push(
+                        token_info["owner"]
+                            .as_str()
+                            .ok_or(format!("{}: token {} has no owner", path.display(), id))?
+                            .to_string(),
+                    );
+                    value.push(parse_value(&token_info["value"], path)?);
+                    yielder.push(parse_value(&token_info["value_in_yielder"], path)?);
+                    offsetter.push(parse_value(&token_info["value_in_offsetter"], path)?);
+                    account.push(token_info["account"].as_str() == Some("true"));
+                }
+            }
+        }
+    }
+
+    Ok(Table {
+        name: "scan",
+        columns: vec![
+            ("project", Column::Text(project)),
+            ("version", Column::Text(version)),
+            ("slot", Column::Text(slot)),
+            ("token_id", Column::Text(token_id)),
+            ("owner", Column::Text(owner)),
+            ("value", Column::Decimal(value, VALUE_SCALE)),
+            ("yielder", Column::Decimal(yielder, VALUE_SCALE)),
+            ("offsetter", Column::Decimal(offsetter, VALUE_SCALE)),
+            ("account", Column::Bool(account)),
+        ],
+    })
+}
+
+/// One row per owner and snapshot file, from `snapshot/*.json`.
+fn aggregate_table() -> Result<Table, Box<dyn Error>> {
+    let mut snapshot = vec![];
+    let mut owner = vec![];
+    let mut value = vec![];
+    let mut yielder = vec![];
+    let mut offsetter = vec![];
+
+    for path in json_paths("snapshot")? {
+        // Some snapshots are empty files, e.g. a project with no v1 holders
+        let Some(json) = read_json(&path)? else {
+            continue;
+        };
+        let name = file_stem(&path);
+        for (address, info) in as_object(&json, &path)? {
+            snapshot.push(name.clone());
+            owner.push(address.clone());
+            value.push(parse_value(&info["value"], &path)?);
+            yielder.push(parse_value(&info["value_yielder"], &path)?);
+            offsetter.push(parse_value(&info["value_offset"], &path)?);
+        }
+    }
+
+    Ok(Table {
+        name: "aggregate",
+        columns: vec![
+            ("snapshot", Column::Text(snapshot)),
+            ("owner", Column::Text(owner)),
+            ("value", Column::Decimal(value, VALUE_SCALE)),
+            ("yielder", Column::Decimal(yielder, VALUE_SCALE)),
+            ("offsetter", Column::Decimal(offsetter, VALUE_SCALE)),
+        ],
+    })
+}
+
+/// One row per owner and project, from `tonnes/*.json`.
+fn allocation_table() -> Result<Table, Box<dyn Error>> {
+    let mut project = vec![];
+    let mut owner = vec![];
+    let mut tonnes = vec![];
+
+    for path in json_paths("tonnes")? {
+        let name = file_stem(&path);
+        for (address, amount) in as_object(&read_required(&path)?, &path)? {
+            project.push(name.clone());
+            owner.push(address.clone());
+            tonnes.push(parse_value(amount, &path)?);
+        }
+    }
+
+    Ok(Table {
+        name: "allocation",
+        columns: vec![
+            ("project", Column::Text(project)),
+            ("owner", Column::Text(owner)),
+            ("tonnes", Column::Decimal(tonnes, TONNES_SCALE)),
+        ],
+    })
+}
+
+fn write_csv(table: &Table, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(table.columns.iter().map(|(name, _)| *name))?;
+
+    let rows = match &table.columns[0].1 {
+        Column::Text(values) => values.len(),
+        Column::Decimal(values, _) => values.len(),
+        Column::Bool(values) => values.len(),
+    };
+    for i in 0..rows {
+        writer.write_record(table.columns.iter().map(|(_, column)| match column {
+            Column::Text(values) => values[i].clone(),
+            Column::Decimal(values, scale) => format_decimal(values[i], *scale),
+            Column::Bool(values) => values[i].to_string(),
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_parquet(table: &Table, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut fields = vec![];
+    let mut arrays: Vec<ArrayRef> = vec![];
+    for (name, column) in &table.columns {
+        match column {
+            Column::Text(values) => {
+                fields.push(Field::new(*name, DataType::Utf8, false));
+                arrays.push(Arc::new(StringArray::from(values.clone())));
+            }
+            Column::Decimal(values, scale) => {
+                fields.push(Field::new(*name, DataType::Decimal128(38, *scale), false));
+                let values = values
+                    .iter()
+                    .map(|v| {
+                        i128::try_from(*v).map_err(|_| format!("{}: {} overflows i128", name, v))
+                    })
+                    .collect::<Result<Vec<i128>, String>>()?;
+                arrays.push(Arc::new(
+                    Decimal128Array::from(values).with_precision_and_scale(38, *scale)?,
+                ));
+            }
+            Column::Bool(values) => {
+                fields.push(Field::new(*name, DataType::Boolean, false));
+                arrays.push(Arc::new(BooleanArray::from(values.clone())));
+            }
+        }
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all("export")?;
+
+    for table in [scan_table()?, aggregate_table()?, allocation_table()?] {
+        let csv_path = "export/".to_owned() + table.name + ".csv";
+        let parquet_path = "export/".to_owned() + table.name + ".parquet";
+        write_csv(&table, Path::new(&csv_path))?;
+        write_parquet(&table, Path::new(&parquet_path))?;
+        println!(
+            "Exported {} to {} and {}",
+            table.name, csv_path, parquet_path
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use clap::Parser;
+use rust_playground::contracts::{by_name, CONTRACTS};
+use serde_json::{from_reader, json, Value};
+use starknet::{
+    core::types::{BlockId, ContractClass, Felt},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider, Url,
+    },
+};
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+/// Fetches the ABI of every scanned contract into `abi/`, warning on upgrades.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Block number to read at, latest when omitted
+    #[arg(long)]
+    block: Option<u64>,
+    #[arg(long, default_value = "https://rpc.starknet.lava.build:443")]
+    rpc: String,
+    #[arg(long, default_value = "abi")]
+    out: String,
+    /// Replace the cached ABI of upgraded contracts, keeping the previous one
+    #[arg(long)]
+    accept_upgrades: bool,
+    /// Also rewrite the ABIs the bindings are generated from, see `BINDINGS`
+    #[arg(long)]
+    bindings: bool,
+}
+
+/// ABI files `rust_playground::bindings` is generated from, with the contract each is
+/// fetched from. Where they came from is recorded in `BINDINGS_SOURCES`.
+const BINDINGS: [(&str, &str); 3] = [
+    ("yielder.abi.json", "BanegasFarmYielder"),
+    ("project.abi.json", "ProjectV2"),
+    ("offsetter.abi.json", "BanegasFarmOffsetter"),
+];
+const BINDINGS_SOURCES: &str = "bindings.sources.json";
+
+/// Cached class of a contract, as recorded in `abi/index.json`.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CachedClass {
+    name: String,
+    class_hash: Felt,
+    block: u64,
+}
+
+/// Extracts the ABI as JSON, Sierra classes carry it as a string.
+fn class_abi(class: &ContractClass) -> Result<Value, Box<dyn std::error::Error>> {
+    match class {
+        ContractClass::Sierra(class) => Ok(serde_json::from_str(&class.abi)?),
+        ContractClass::Legacy(class) => Ok(json!(class.abi)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(&args.rpc)?));
+    println!("Provider initialized successfully");
+
+    // Pin every call to the same block so the classes are consistent
+    let block_number = match args.block {
+        Some(block) => block,
+        None => provider.block_number().await?,
+    };
+    let block_id = BlockId::Number(block_number);
+    println!("Fetching classes at block {}", block_number);
+
+    let dir = Path::new(&args.out);
+    std::fs::create_dir_all(dir)?;
+    let index_path = dir.join("index.json");
+    let mut index: BTreeMap<String, CachedClass> = match File::open(&index_path) {
+        Ok(file) => from_reader(file)?,
+        Err(_) => BTreeMap::new(),
+    };
+
+    let mut upgraded = vec![];
+    for (name, address) in CONTRACTS {
+        let address_hex = address.to_fixed_hex_string();
+        let class_hash = provider.get_class_hash_at(block_id, address).await?;
+        let reviewed = dir.join(address_hex.clone() + ".json");
+
+        if let Some(cached) = index.get(&address_hex) {
+            if cached.class_hash == class_hash {
+                println!("{} unchanged ({})", name, class_hash.to_hex_string());
+                continue;
+            }
+            eprintln!(
+                "WARNING: {} ({}) class hash changed from {} at block {} to {}",
+                name,
+                address_hex,
+                cached.class_hash.to_hex_string(),
+                cached.block,
+                class_hash.to_hex_string()
+            );
+            // The new ABI is kept next to the reviewed one until the upgrade is accepted
+            let class = provider.get_class_at(block_id, address).await?;
+            let upgrade = dir.join(format!(
+                "{}.{}.json",
+                address_hex,
+                class_hash.to_fixed_hex_string()
+            ));
+            std::fs::write(&upgrade, serde_json::to_string_pretty(&class_abi(&class)?)?)?;
+            if !args.accept_upgrades {
+                eprintln!("  new ABI in {}", upgrade.display());
+                upgraded.push(name);
+                continue;
+            }
+            let previous = dir.join(format!(
+                "{}.{}.json",
+                address_hex,
+                cached.class_hash.to_fixed_hex_string()
+            ));
+            std::fs::rename(&reviewed, &previous)?;
+            std::fs::rename(&upgrade, &reviewed)?;
+            println!(
+                "{} upgrade accepted, previous ABI kept in {}",
+                name,
+                previous.display()
+            );
+        } else {
+            let class = provider.get_class_at(block_id, address).await?;
+            std::fs::write(
+                &reviewed,
+                serde_json::to_string_pretty(&class_abi(&class)?)?,
+            )?;
+            println!("{} cached ({})", name, class_hash.to_hex_string());
+        }
+        index.insert(
+            address_hex,
+            CachedClass {
+                name: name.to_string(),
+                class_hash,
+                block: block_number,
+            },
+        );
+    }
+
+    std::fs::write(&index_path, serde_json::to_string_pretty(&index)?)?;
+
+    // Until they are accepted, upgrades fail every run and the bindings are left alone
+    if !upgraded.is_empty() {
+        return Err(format!(
+            "{} contract(s) upgraded since the cached ABI, review the new ABIs and rerun with --accept-upgrades: {:?}",
+            upgraded.len(),
+            upgraded
+        )
+        .into());
+    }
+
+    if args.bindings {
+        let mut sources = BTreeMap::new();
+        for (file, name) in BINDINGS {
+            let address = &by_name(name).ok_or(format!("{} is not a fetched contract", name))?;
+            let class_hash = provider.get_class_hash_at(block_id, address).await?;
+            let class = provider.get_class_at(block_id, address).await?;
+            std::fs::write(
+                file,
+                serde_json::to_string_pretty(&class_abi(&class)?)? + "\n",
+            )?;
+            println!("{} written from {}", file, name);
+            sources.insert(
+                file,
+                json!({
+                    "contract": name,
+                    "address": address.to_fixed_hex_string(),
+                    "class_hash": class_hash.to_hex_string(),
+                    "block": block_number,
+                }),
+            );
+        }
+        std::fs::write(
+            BINDINGS_SOURCES,
+            serde_json::to_string_pretty(&sources)? + "\n",
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,403 @@
+use clap::Parser;
+use rust_playground::migration::load_batches;
+use starknet::{
+    accounts::{Account, ConnectedAccount, ExecutionEncoding, SingleOwnerAccount},
+    core::types::{BlockId, BlockTag, Call, ExecutionResult, FeeEstimate, Felt, StarknetError},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider, ProviderError, Url,
+    },
+    signers::{LocalWallet, SigningKey},
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+/// Signs and submits the batches built by `batches`, journaling every transaction.
+///
+/// Defaults target a local starknet-devnet so a migration can be rehearsed
+/// before running it on mainnet.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Project name, as in `batches/<project>/`
+    #[arg(long)]
+    project: String,
+    #[arg(long, default_value = "http://127.0.0.1:5050")]
+    rpc: String,
+    /// Account submitting the batches, its private key is read from `PRIVATE_KEY`
+    #[arg(long)]
+    account: String,
+    #[arg(long, default_value = "batches")]
+    dir: String,
+    /// Seconds to wait for a receipt before marking the batch `unknown`
+    #[arg(long, default_value_t = 600)]
+    receipt_timeout: u64,
+    /// Resubmit batches journaled as `reverted` or `unknown` instead of stopping on them
+    #[arg(long)]
+    retry: bool,
+}
+
+const RECEIPT_POLL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    /// Signed with a pinned nonce and journaled, maybe not sent yet
+    Submitting,
+    Submitted,
+    Succeeded,
+    Reverted(String),
+    /// No receipt before the timeout, the transaction may have been dropped
+    Unknown,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct JournalEntry {
+    checksum: Felt,
+    tx_hash: Felt,
+    /// Account nonce the transaction was signed with
+    nonce: Felt,
+    status: Status,
+    /// Transactions of earlier attempts, replaced by a `--retry`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    replaced: Vec<Felt>,
+}
+
+/// What to do with a batch given its journal entry.
+#[derive(Debug, PartialEq)]
+enum Step {
+    Skip,
+    Wait(Felt),
+    /// Sign and send, with the given nonce or else the next one of the account
+    Submit(Option<Felt>),
+    /// Find out on chain whether the journaled transaction, or another one with
+    /// its nonce, went through before sending it again
+    Reconcile,
+}
+
+fn next_step(entry: Option<&JournalEntry>, checksum: Felt, retry: bool) -> Result<Step, String> {
+    let Some(entry) = entry else {
+        return Ok(Step::Submit(None));
+    };
+    if entry.checksum != checksum {
+        return Err(format!(
+            "rebuilt since it was submitted in {}",
+            entry.tx_hash.to_hex_string()
+        ));
+    }
+    match &entry.status {
+        Status::Succeeded => Ok(Step::Skip),
+        // The run stopped between journaling and sending, or lost the response
+        Status::Submitting => Ok(Step::Reconcile),
+        // Submitted on a previous run, wait for it instead of sending it twice
+        Status::Submitted => Ok(Step::Wait(entry.tx_hash)),
+        // A reverted transaction used its nonce and changed nothing, send it again
+        Status::Reverted(_) if retry => Ok(Step::Submit(None)),
+        Status::Unknown if retry => Ok(Step::Reconcile),
+        Status::Unknown => Ok(Step::Wait(entry.tx_hash)),
+        Status::Reverted(reason) => Err(format!(
+            "reverted in {}: {}, rerun with --retry once fixed",
+            entry.tx_hash.to_hex_string(),
+            reason
+        )),
+    }
+}
+
+type Journal = BTreeMap<usize, JournalEntry>;
+
+fn load_journal(path: &Path) -> Result<Journal, Box<dyn std::error::Error>> {
+    match File::open(path) {
+        Ok(file) => {
+            serde_json::from_reader(file).map_err(|e| format!("{}: {}", path.display(), e).into())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Journal::new()),
+        Err(e) => Err(format!("{}: {}", path.display(), e).into()),
+    }
+}
+
+fn save_journal(path: &Path, journal: &Journal) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(path, serde_json::to_string_pretty(journal)?)?;
+    Ok(())
+}
+
+/// Waits for the journaled transaction when the node knows it, or sends the batch
+/// again with the same nonce when that nonce is still unused. Fails when another
+/// transaction used the nonce, which needs a look at the account.
+async fn reconcile(
+    provider: &JsonRpcClient<HttpTransport>,
+    account: Felt,
+    entry: &JournalEntry,
+) -> Result<Step, Box<dyn std::error::Error>> {
+    match provider.get_transaction_status(entry.tx_hash).await {
+        Ok(_) => return Ok(Step::Wait(entry.tx_hash)),
+        Err(ProviderError::StarknetError(StarknetError::TransactionHashNotFound)) => {}
+        Err(e) => return Err(e.into()),
+    }
+    let nonce = provider
+        .get_nonce(BlockId::Tag(BlockTag::Pending), account)
+        .await?;
+    if nonce > entry.nonce {
+        return Err(format!(
+            "nonce {} was used but {} is unknown to the node, check the account before resuming",
+            entry.nonce,
+            entry.tx_hash.to_hex_string()
+        )
+        .into());
+    }
+    Ok(Step::Submit(Some(entry.nonce)))
+}
+
+/// Gas and gas price to sign with, 1.5 times the estimate as `starknet-rs` does when
+/// it estimates on its own.
+fn gas_bounds(estimate: &FeeEstimate) -> Result<(u64, u128), Box<dyn std::error::Error>> {
+    let overall_fee = u128::try_from(estimate.overall_fee)?;
+    let gas_price = u128::try_from(estimate.gas_price)?;
+    if gas_price == 0 {
+        return Err("fee estimate has a zero gas price".into());
+    }
+    let gas = u64::try_from(overall_fee.div_ceil(gas_price) * 3 / 2)?;
+    Ok((gas, gas_price * 3 / 2))
+}
+
+/// Polls for the receipt of `tx_hash`, `Status::Unknown` if none shows up within `timeout`.
+async fn wait_for_receipt(
+    provider: &JsonRpcClient<HttpTransport>,
+    tx_hash: Felt,
+    timeout: Duration,
+) -> Result<Status, Box<dyn std::error::Error>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match provider.get_transaction_receipt(tx_hash).await {
+            Ok(receipt) => {
+                return Ok(match receipt.receipt.execution_result() {
+                    ExecutionResult::Succeeded => Status::Succeeded,
+                    ExecutionResult::Reverted { reason } => Status::Reverted(reason.clone()),
+                })
+            }
+            Err(ProviderError::StarknetError(StarknetError::TransactionHashNotFound)) => {
+                if Instant::now() >= deadline {
+                    return Ok(Status::Unknown);
+                }
+                sleep(RECEIPT_POLL).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(&args.rpc)?));
+    let chain_id = provider.chain_id().await?;
+    let signer = LocalWallet::from(SigningKey::from_secret_scalar(Felt::from_hex(
+        &std::env::var("PRIVATE_KEY").expect("PRIVATE_KEY should be set"),
+    )?));
+    let account = SingleOwnerAccount::new(
+        JsonRpcClient::new(HttpTransport::new(Url::parse(&args.rpc)?)),
+        signer,
+        Felt::from_hex(&args.account)?,
+        chain_id,
+        ExecutionEncoding::New,
+    );
+    println!("Account initialized successfully");
+
+    let dir = Path::new(&args.dir).join(&args.project);
+    let journal_path = dir.join("journal.json");
+    let mut journal = load_journal(&journal_path)?;
+    let batches = load_batches(&dir)?;
+    println!("Loaded {} batches for {}", batches.len(), args.project);
+
+    for batch in batches {
+        let entry = journal.get(&batch.index);
+        let step = next_step(entry, batch.checksum, args.retry)
+            .map_err(|e| format!("batch {} {}", batch.index, e))?;
+        // A batch is only sent again with the nonce it was signed with, unless that
+        // transaction reverted, so at most one of its transactions can execute
+        let step = match (step, entry) {
+            (Step::Reconcile, Some(entry)) => reconcile(&provider, account.address(), entry)
+                .await
+                .map_err(|e| format!("batch {}: {}", batch.index, e))?,
+            (step, _) => step,
+        };
+        let tx_hash = match step {
+            Step::Skip => {
+                println!("Batch {} already confirmed, skipping", batch.index);
+                continue;
+            }
+            Step::Wait(tx_hash) => tx_hash,
+            Step::Reconcile => unreachable!("only journaled batches are reconciled"),
+            Step::Submit(pinned_nonce) => {
+                let calls: Vec<Call> = batch
+                    .calls
+                    .iter()
+                    .map(|call| Call {
+                        to: call.contract_address,
+                        selector: call.entry_point_selector,
+                        calldata: call.calldata.clone(),
+                    })
+                    .collect();
+                let nonce = match pinned_nonce {
+                    Some(nonce) => nonce,
+                    None => account.get_nonce().await?,
+                };
+                let estimate = account
+                    .execute_v3(calls.clone())
+                    .nonce(nonce)
+                    .estimate_fee()
+                    .await?;
+                let (gas, gas_price) = gas_bounds(&estimate)?;
+                let execution = account
+                    .execute_v3(calls)
+                    .nonce(nonce)
+                    .gas(gas)
+                    .gas_price(gas_price)
+                    .prepared()?;
+                let tx_hash = execution.transaction_hash(false);
+                let mut replaced = entry.map(|e| e.replaced.clone()).unwrap_or_default();
+                if let Some(entry) = entry.filter(|entry| entry.tx_hash != tx_hash) {
+                    println!(
+                        "Sending batch {} again, replacing {}",
+                        batch.index,
+                        entry.tx_hash.to_hex_string()
+                    );
+                    replaced.push(entry.tx_hash);
+                }
+
+                // Journaled before sending, so a crash or a lost response is reconciled
+                journal.insert(
+                    batch.index,
+                    JournalEntry {
+                        checksum: batch.checksum,
+                        tx_hash,
+                        nonce,
+                        status: Status::Submitting,
+                        replaced,
+                    },
+                );
+                save_journal(&journal_path, &journal)?;
+
+                let result = execution.send().await?;
+                if result.transaction_hash != tx_hash {
+                    return Err(format!(
+                        "batch {}: node returned {} for transaction {}",
+                        batch.index,
+                        result.transaction_hash.to_hex_string(),
+                        tx_hash.to_hex_string()
+                    )
+                    .into());
+                }
+                journal.get_mut(&batch.index).unwrap().status = Status::Submitted;
+                save_journal(&journal_path, &journal)?;
+                tx_hash
+            }
+        };
+        println!(
+            "Batch {} submitted in {}, waiting for receipt...",
+            batch.index,
+            tx_hash.to_hex_string()
+        );
+
+        let status = wait_for_receipt(
+            &provider,
+            tx_hash,
+            Duration::from_secs(args.receipt_timeout),
+        )
+        .await?;
+        journal.get_mut(&batch.index).unwrap().status = status.clone();
+        save_journal(&journal_path, &journal)?;
+
+        match status {
+            Status::Reverted(reason) => {
+                return Err(format!("batch {} reverted: {}", batch.index, reason).into());
+            }
+            Status::Unknown => {
+                return Err(format!(
+                    "batch {} has no receipt after {}s, rerun to wait again or --retry to resubmit",
+                    batch.index, args.receipt_timeout
+                )
+                .into());
+            }
+            _ => println!("Batch {} confirmed", batch.index),
+        }
+    }
+
+    println!("All batches confirmed for {}", args.project);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(status: Status) -> JournalEntry {
+        JournalEntry {
+            checksum: Felt::ONE,
+            tx_hash: Felt::TWO,
+            nonce: Felt::from(5),
+            status,
+            replaced: vec![],
+        }
+    }
+
+    #[test]
+    fn next_step_follows_the_journal() {
+        assert_eq!(next_step(None, Felt::ONE, false), Ok(Step::Submit(None)));
+        let succeeded = entry(Status::Succeeded);
+        assert_eq!(next_step(Some(&succeeded), Felt::ONE, true), Ok(Step::Skip));
+        let submitted = entry(Status::Submitted);
+        assert_eq!(
+            next_step(Some(&submitted), Felt::ONE, true),
+            Ok(Step::Wait(Felt::TWO))
+        );
+        assert!(next_step(Some(&submitted), Felt::THREE, false).is_err());
+        let submitting = entry(Status::Submitting);
+        assert_eq!(
+            next_step(Some(&submitting), Felt::ONE, false),
+            Ok(Step::Reconcile)
+        );
+    }
+
+    #[test]
+    fn only_retry_resubmits_failed_batches() {
+        let reverted = entry(Status::Reverted("out of gas".to_string()));
+        assert!(next_step(Some(&reverted), Felt::ONE, false).is_err());
+        assert_eq!(
+            next_step(Some(&reverted), Felt::ONE, true),
+            Ok(Step::Submit(None))
+        );
+        let unknown = entry(Status::Unknown);
+        assert_eq!(
+            next_step(Some(&unknown), Felt::ONE, false),
+            Ok(Step::Wait(Felt::TWO))
+        );
+        assert_eq!(
+            next_step(Some(&unknown), Felt::ONE, true),
+            Ok(Step::Reconcile)
+        );
+    }
+
+    #[test]
+    fn gas_bounds_add_a_margin() {
+        let estimate = FeeEstimate {
+            gas_consumed: Felt::from(90),
+            gas_price: Felt::from(10),
+            data_gas_consumed: Felt::ZERO,
+            data_gas_price: Felt::ZERO,
+            overall_fee: Felt::from(1000),
+            unit: starknet::core::types::PriceUnit::Fri,
+        };
+        assert_eq!(gas_bounds(&estimate).unwrap(), (150, 15));
+        let free = FeeEstimate {
+            gas_price: Felt::ZERO,
+            ..estimate
+        };
+        assert!(gas_bounds(&free).is_err());
+    }
+}
@@ -1,6 +1,25 @@
-use serde_json::json;
+use cainome::cairo_serde::{CairoSerde, ContractAddress, U256};
+use rust_playground::{
+    bindings::{offsetter::OffsetterReader, project::ProjectReader, yielder::YielderReader},
+    contracts::{
+        BANEGAS_OFFSETTER, BANEGAS_V1, BANEGAS_YIELDER, KARATHURU_V2, LAS_DELICIAS_OFFSETTER,
+        LAS_DELICIAS_V1, LAS_DELICIAS_YIELDER, MANJARISOA_OFFSETTER, MANJARISOA_YIELDER,
+        MANJARISO_BRONZE_V1, MANJARISO_GOLD_V1, MANJARISO_SILVER_V1, PROJECT_V2,
+    },
+    decode::{decode_string, decode_transfer, u256_to_felt, u256_to_u128},
+    entrypoints::{classify, CallFailure, Selectors},
+    erc3525, erc721,
+    events::Publisher,
+    exclusions::{load_exclusions, token_rule, Exclusion},
+    rpc::{yielder_depositors, Batcher, CachedTransport, RATE_LIMIT},
+    store,
+};
+use serde_json::{json, Value};
 use starknet::{
-    core::types::{BlockId, BlockTag, Felt, FunctionCall},
+    core::{
+        crypto::compute_hash_on_elements,
+        types::{BlockHashAndNumber, BlockId, Felt, FunctionCall, MaybePendingBlockWithTxHashes},
+    },
     macros::{felt, selector},
     providers::{
         jsonrpc::{HttpTransport, JsonRpcClient},
@@ -8,9 +27,9 @@ use starknet::{
     },
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     str::FromStr,
-    time::{Duration, Instant},
+    time::Instant,
     vec,
 };
 use tokio::time::sleep;
@@ -22,6 +41,9 @@ struct TokenInfo {
     account: String,
     value_in_yielder: String,
     value_in_offsetter: String,
+    /// Why every `supportsInterface` call on the owner failed, absent when one answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_check: Option<CallFailure>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -33,357 +55,645 @@ struct ProjectInfo {
     offsetter: Felt,
 }
 
-pub const RATE_LIMIT: Duration = Duration::from_millis(100);
-pub const MULTICALL_CONTRACT: Felt =
-    felt!("0x0038e22d0a15703176262dd457a56e5176d13acdfa206d8d397e405223552c9c");
-
-async fn aggregate_calls(
-    provider: &JsonRpcClient<HttpTransport>,
-    calls: Vec<FunctionCall>,
-) -> Result<Vec<Felt>, Box<dyn std::error::Error>> {
-    let mut calldata = vec![calls.len().into()];
-    for call in calls {
-        calldata.push(call.contract_address);
-        calldata.push(call.entry_point_selector);
-        calldata.push(call.calldata.len().into());
-        calldata.extend(call.calldata);
-    }
-    let res = provider.call(
-        FunctionCall {
-            contract_address: MULTICALL_CONTRACT,
-            entry_point_selector: selector!("aggregate"),
-            calldata,
-        },
-        BlockId::Tag(BlockTag::Latest),
-    );
-    let res = res.await?;
+/// Unit value of a v1 collection, every v1 token is one unit.
+/// The configured price must match the minter's at the pinned block.
+#[derive(Debug)]
+struct V1Pricing {
+    /// Found from the mint transactions when not set
+    minter: Option<Felt>,
+    /// USDC (6 decimals) per token
+    unit_price: u128,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct RewardInfo {
+    claimable: String,
+    claimed: String,
+}
+
+/// USDC rewards of a yielder, per depositor and in total.
+#[derive(Debug, serde::Serialize)]
+struct YielderRewards {
+    yielder: String,
+    total_claimable: String,
+    total_claimed: String,
+    /// Sums over `depositors`, which should match the yielder totals
+    claimable_sum: String,
+    claimed_sum: String,
+    consistent: bool,
+    depositors: HashMap<String, RewardInfo>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ExcludedToken {
+    project: String,
+    version: String,
+    token_id: String,
+    value: String,
+    reason: String,
+    /// The rule's reason is still to be confirmed
+    unconfirmed: bool,
+}
+
+/// Exclusion rules, with the tokens they removed from the scan.
+struct Exclusions {
+    rules: Vec<Exclusion>,
+    excluded: Vec<ExcludedToken>,
+}
+
+impl Exclusions {
+    fn rule(&self, version: &str, project: &ProjectInfo, token_id: &Felt) -> Option<&Exclusion> {
+        token_rule(
+            &self.rules,
+            version,
+            &project.address,
+            &project.slot,
+            token_id,
+        )
+    }
+
+    /// Records `token_id` as excluded when a rule applies to it.
+    fn exclude(
+        &mut self,
+        version: &str,
+        project: &ProjectInfo,
+        token_id: &Felt,
+        value: u128,
+    ) -> bool {
+        let Some(rule) = self.rule(version, project, token_id) else {
+            return false;
+        };
+        let token = ExcludedToken {
+            project: project.name.clone(),
+            version: version.to_string(),
+            token_id: token_id.to_bigint().to_string(),
+            value: value.to_string(),
+            reason: rule.reason().to_string(),
+            unconfirmed: rule.unconfirmed(),
+        };
+        self.excluded.push(token);
+        true
+    }
+}
+
+/// What the minter of a v1 collection sold, from its mint transactions up to the
+/// pinned block. Tokens minted without a payment to the minter, e.g. airdrops,
+/// are counted apart.
+#[derive(Debug, serde::Serialize)]
+struct V1Sales {
+    minter: String,
+    /// `getUnitPrice` of the minter at the pinned block
+    unit_price: String,
+    sold_tokens: u64,
+    /// USDC paid to the minter in the transactions minting `sold_tokens`
+    sold_value: String,
+    unpaid_tokens: u64,
+    /// `sold_value` is `sold_tokens` × `unit_price`
+    consistent: bool,
+}
+
+/// Mint transactions of `contract` up to `block_id`, with the tokens each minted.
+async fn mint_transactions(
+    provider: &JsonRpcClient<CachedTransport>,
+    contract: Felt,
+    block_id: BlockId,
+) -> Result<Vec<(Felt, u64)>, Box<dyn std::error::Error>> {
+    let mut mints: Vec<(Felt, u64)> = vec![];
+    for transfer in erc721::transfers(provider, contract, block_id).await? {
+        if transfer.from != Felt::ZERO {
+            continue;
+        }
+        // Events come in order, the tokens of one transaction are contiguous
+        match mints.last_mut() {
+            Some((tx, minted)) if *tx == transfer.transaction_hash => *minted += 1,
+            _ => mints.push((transfer.transaction_hash, 1)),
+        }
+    }
+    Ok(mints)
+}
+
+/// Whether `candidate` is a minter of `project`, from its `getCarbonableProjectAddress`.
+async fn is_minter_of(
+    provider: &JsonRpcClient<CachedTransport>,
+    candidate: Felt,
+    project: &ProjectInfo,
+    block_id: BlockId,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let call = FunctionCall {
+        contract_address: candidate,
+        entry_point_selector: selector!("getCarbonableProjectAddress"),
+        calldata: vec![],
+    };
+    let result = provider.call(call, block_id).await;
     sleep(RATE_LIMIT).await; // Rate limit delay
-    Ok(res)
+    match result {
+        Ok(data) => Ok(data.first() == Some(&project.address)),
+        // Accounts and tokens paid in the same transaction are not minters
+        Err(error) if classify(&error).is_some() => Ok(false),
+        Err(error) => Err(error.into()),
+    }
 }
 
-async fn scan_project(
-    provider: &JsonRpcClient<HttpTransport>,
+/// Reads the sales of the minter of a v1 collection and its unit price at `block_id`,
+/// failing if the price differs from the configured one. The minter is the
+/// configured one, or else the first contract paid in a mint transaction that
+/// names the collection as its project.
+async fn read_minter_sales(
+    provider: &JsonRpcClient<CachedTransport>,
     project: &ProjectInfo,
-) -> Result<HashMap<String, HashMap<String, TokenInfo>>, Box<dyn std::error::Error>> {
-    println!("\nScanning project: {}", project.name);
-    let mut slot_tokens = HashMap::new();
-    let mut slot_map = HashMap::new();
+    pricing: &V1Pricing,
+    block_id: BlockId,
+) -> Result<V1Sales, Box<dyn std::error::Error>> {
+    let mints = mint_transactions(provider, project.address, block_id).await?;
+    println!("{}: {} mint transactions", project.name, mints.len());
 
-    // Get total supply
-    let total_supply_call_result = provider
+    let mut minter = pricing.minter;
+    let mut not_minters = BTreeSet::new();
+    let (mut sold_tokens, mut sold_value, mut unpaid_tokens) = (0, 0_u128, 0);
+    for (tx, minted) in mints {
+        let receipt = provider.get_transaction_receipt(tx).await?;
+        sleep(RATE_LIMIT).await; // Rate limit delay
+
+        let mut paid = 0_u128;
+        for event in receipt.receipt.events() {
+            // Payments are the transfers of any other contract
+            if event.from_address == project.address
+                || event.keys.first() != Some(&selector!("Transfer"))
+            {
+                continue;
+            }
+            let Some((_, to, amount)) = decode_transfer(&event.keys, &event.data) else {
+                continue;
+            };
+            if minter.is_none() && !not_minters.contains(&to) {
+                if is_minter_of(provider, to, project, block_id).await? {
+                    println!("{}: minter {}", project.name, to.to_hex_string());
+                    minter = Some(to);
+                } else {
+                    not_minters.insert(to);
+                }
+            }
+            if minter == Some(to) {
+                paid += u256_to_u128(&amount)?;
+            }
+        }
+        if paid > 0 {
+            sold_tokens += minted;
+            sold_value += paid;
+        } else {
+            unpaid_tokens += minted;
+        }
+    }
+    let minter = minter.ok_or(format!(
+        "{}: no mint transaction paid a minter, set its `minter`",
+        project.name
+    ))?;
+
+    let price = provider
         .call(
             FunctionCall {
-                contract_address: project.address,
-                entry_point_selector: selector!("totalSupply"),
+                contract_address: minter,
+                entry_point_selector: selector!("getUnitPrice"),
                 calldata: vec![],
             },
-            BlockId::Tag(BlockTag::Latest),
+            block_id,
         )
         .await?;
-    let total_supply: usize = total_supply_call_result[0]
-        .to_bigint()
-        .to_string()
-        .parse()
-        .unwrap_or(0_usize);
+    sleep(RATE_LIMIT).await; // Rate limit delay
+    let unit_price = u256_to_u128(&U256::cairo_deserialize(&price, 0)?)?;
+    if unit_price != pricing.unit_price {
+        return Err(format!(
+            "{}: minter {} unit price {} does not match configured {}",
+            project.name,
+            minter.to_hex_string(),
+            unit_price,
+            pricing.unit_price
+        )
+        .into());
+    }
+
+    Ok(V1Sales {
+        minter: minter.to_hex_string(),
+        unit_price: unit_price.to_string(),
+        sold_tokens,
+        sold_value: sold_value.to_string(),
+        unpaid_tokens,
+        consistent: sold_value == sold_tokens as u128 * unit_price,
+    })
+}
+
+/// Collection metadata recorded in the snapshot header.
+#[derive(Debug, Default, serde::Serialize)]
+struct ProjectMetadata {
+    address: String,
+    slot: String,
+    name: Option<String>,
+    symbol: Option<String>,
+    uri: Option<String>,
+    value_decimals: Option<u8>,
+}
+
+/// Reads a string entrypoint, `None` when the contract does not expose it.
+async fn read_string(
+    provider: &JsonRpcClient<CachedTransport>,
+    selectors: &mut Selectors,
+    contract: Felt,
+    name: &str,
+    calldata: Vec<Felt>,
+    block_id: BlockId,
+) -> Option<String> {
+    match selectors
+        .call(provider, contract, name, calldata, block_id)
+        .await
+    {
+        Ok(data) => decode_string(&data),
+        Err(e) => {
+            eprintln!("No {} for {}: {}", name, contract.to_hex_string(), e);
+            None
+        }
+    }
+}
+
+/// Reads name, symbol, URI and value decimals, leaving out any the contract does not expose.
+/// `uri` is the URI entrypoint and its calldata, `slot_uri` for v2 and `tokenURI` for v1.
+async fn read_metadata(
+    provider: &JsonRpcClient<CachedTransport>,
+    selectors: &mut Selectors,
+    project: &ProjectInfo,
+    uri: (&str, Vec<Felt>),
+    block_id: BlockId,
+) -> ProjectMetadata {
+    let address = project.address;
+    let name = read_string(provider, selectors, address, "name", vec![], block_id).await;
+    let symbol = read_string(provider, selectors, address, "symbol", vec![], block_id).await;
+    let uri = read_string(provider, selectors, address, uri.0, uri.1, block_id).await;
+    let value_decimals = selectors
+        .call(provider, address, "value_decimals", vec![], block_id)
+        .await
+        .ok()
+        .and_then(|data| data.first()?.to_bigint().to_string().parse().ok());
+
+    ProjectMetadata {
+        address: address.to_hex_string(),
+        slot: project.slot.to_string(),
+        name,
+        symbol,
+        uri,
+        value_decimals,
+    }
+}
+
+/// Fails unless every token of the on-chain `supply` is either held by a non-zero
+/// owner in `holders` or was removed by one of the `excluded` documented exclusions.
+/// Duplicate ids collapse into one holder, so they fail too.
+fn check_enumeration(
+    project: &ProjectInfo,
+    supply: usize,
+    excluded: usize,
+    holders: &HashMap<String, TokenInfo>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let held = holders
+        .values()
+        .filter(|token| Felt::from_hex(&token.owner).is_ok_and(|owner| owner != Felt::ZERO))
+        .count();
+    if held + excluded != supply {
+        return Err(format!(
+            "{}: {} held and {} excluded tokens but on-chain supply is {}",
+            project.name, held, excluded, supply
+        )
+        .into());
+    }
+    Ok(())
+}
 
-    // get token ids
-    let mut calls: Vec<FunctionCall> = vec![];
-    for index in 0..total_supply {
-        let calldata = vec![Felt::from(index), Felt::ZERO];
+/// Publishes a project's completion, and a verification failure for every
+/// token whose owner did not answer as an account.
+async fn publish_project(
+    publisher: &Publisher,
+    version: &str,
+    project: &ProjectInfo,
+    tokens: &HashMap<String, HashMap<String, TokenInfo>>,
+) {
+    for slot_map in tokens.values() {
+        for (token_id, token) in slot_map.iter() {
+            if token.account == "false" {
+                publisher
+                    .publish(
+                        "verification.failed",
+                        json!({
+                            "version": version,
+                            "project": project.name,
+                            "token_id": token_id,
+                            "owner": token.owner,
+                            "reason": "owner is not an account",
+                        }),
+                    )
+                    .await;
+            }
+        }
+    }
+    publisher
+        .publish(
+            "project.completed",
+            json!({
+                "version": version,
+                "project": project.name,
+                "address": project.address.to_hex_string(),
+                "slot": project.slot.to_string(),
+                "totals": project_totals(tokens),
+            }),
+        )
+        .await;
+}
+
+fn project_totals(tokens: &HashMap<String, HashMap<String, TokenInfo>>) -> Value {
+    let mut token_count = 0;
+    let mut total_value: u128 = 0;
+    for slot_map in tokens.values() {
+        for token in slot_map.values() {
+            token_count += 1;
+            total_value += token.value.parse::<u128>().unwrap_or(0);
+        }
+    }
+    json!({ "tokens": token_count, "value": total_value.to_string() })
+}
+
+/// Interface ids an account may support: SRC-6, and the two Cairo 0 account ids.
+const ACCOUNT_INTERFACES: [Felt; 3] = [
+    felt!("0x2ceccef7f994940b3962a6c67e0ba4fcd37df7d131417c604f91e03caecc1cd"),
+    felt!("0xa66bd575"),
+    felt!("0xf10dbd44"),
+];
+
+/// Whether each owner supports one of the account interfaces, with why every check
+/// failed when none answered. Owners that are not deployed, or whose class lists
+/// no `supportsInterface`, fail without a call. Other failures are classified
+/// rather than fatal. Classes are read in batches and shared across projects.
+async fn check_accounts(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    selectors: &mut Selectors,
+    owners: &[Felt],
+    block_id: BlockId,
+) -> Result<Vec<(bool, Option<CallFailure>)>, Box<dyn std::error::Error>> {
+    selectors.prefetch(batcher, owners, block_id).await?;
+    let mut owner_selectors = vec![];
+    for owner in owners {
+        let selector = selectors
+            .selector(provider, *owner, "supportsInterface", block_id)
+            .await;
+        owner_selectors.push(selector.unwrap_or_else(|e| {
+            println!("No class for owner {}: {}", owner.to_hex_string(), e);
+            Err(CallFailure::Unanswered)
+        }));
+    }
 
-        calls.push(FunctionCall {
-            contract_address: project.address,
-            entry_point_selector: selector!("tokenByIndex"),
-            calldata,
-        });
+    let mut checks = vec![vec![]; owners.len()];
+    for interface in ACCOUNT_INTERFACES {
+        let calls: Vec<FunctionCall> = owners
+            .iter()
+            .zip(&owner_selectors)
+            .filter_map(|(owner, selector)| {
+                Some(FunctionCall {
+                    contract_address: *owner,
+                    entry_point_selector: *selector.as_ref().ok()?,
+                    calldata: vec![interface],
+                })
+            })
+            .collect();
+        let mut results = batcher
+            .try_aggregate(provider, &calls, block_id)
+            .await?
+            .into_iter();
+        for (check, selector) in checks.iter_mut().zip(&owner_selectors) {
+            check.push(match selector {
+                Ok(_) => results.next().ok_or("missing supportsInterface result")?,
+                Err(failure) => Err(*failure),
+            });
+        }
     }
 
+    Ok(checks.iter().map(|check| account_check(check)).collect())
+}
+
+/// Whether one of the `supportsInterface` checks of an owner answered true, with
+/// the failure of the first check when none answered.
+fn account_check(checks: &[Result<Vec<Felt>, CallFailure>]) -> (bool, Option<CallFailure>) {
+    let support = checks
+        .iter()
+        .any(|result| matches!(result, Ok(data) if data.first() == Some(&Felt::ONE)));
+    let failure = match checks.iter().all(Result::is_err) {
+        true => checks.first().and_then(|result| result.clone().err()),
+        false => None,
+    };
+    (support, failure)
+}
+
+async fn scan_project(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    selectors: &mut Selectors,
+    project: &ProjectInfo,
+    unit_value: u128,
+    exclusions: &mut Exclusions,
+    block_id: BlockId,
+) -> Result<HashMap<String, HashMap<String, TokenInfo>>, Box<dyn std::error::Error>> {
+    println!("\nScanning project: {}", project.name);
+    let mut slot_tokens = HashMap::new();
+    let mut slot_map = HashMap::new();
+
     println!("Fetching token ids...");
-    let token_ids = aggregate_calls(provider, calls).await?;
+    let (_, token_ids) =
+        erc721::token_ids(provider, batcher, selectors, project.address, block_id).await?;
+    let enumerated = token_ids.len();
+
+    println!("Unit value for {}: {}", project.name, unit_value);
 
-    let token_ids: Vec<Felt> = token_ids[2..]
-        .chunks(3)
-        .map(|chunk| chunk[1].clone())
-        .filter(|id| !vec!["2055"].contains(&id.to_bigint().to_string().as_str()))
+    let token_ids: Vec<Felt> = token_ids
+        .into_iter()
+        .filter(|id| !exclusions.exclude("v1", project, id, unit_value))
         .collect();
 
-    println!("Token ids: {:?}", token_ids);
+    let excluded_count = enumerated - token_ids.len();
+    println!(
+        "Token ids: {:?} ({} of {} after exclusions)",
+        token_ids,
+        token_ids.len(),
+        enumerated
+    );
 
+    let owner_of = match token_ids.first() {
+        Some(id) => {
+            let probe = vec![*id, Felt::ZERO];
+            selectors
+                .resolve(provider, project.address, "ownerOf", probe, block_id)
+                .await?
+        }
+        None => selector!("ownerOf"),
+    };
     let calls: Vec<FunctionCall> = token_ids
         .iter()
         .flat_map(|id| {
             vec![FunctionCall {
                 contract_address: project.address,
-                entry_point_selector: selector!("ownerOf"),
+                entry_point_selector: owner_of,
                 calldata: vec![*id, Felt::ZERO],
             }]
         })
         .collect();
 
     println!("Fetching token data...");
-    let results = aggregate_calls(provider, calls).await?;
+    let results = batcher.aggregate(provider, &calls, block_id).await?;
 
-    for (i, data) in results[2..].chunks(2).enumerate() {
-        let owner_result = data[1].to_hex_string();
-        let token_id = token_ids[i].to_bigint().to_string();
+    for (id, data) in token_ids.iter().zip(results) {
+        let owner = ContractAddress::cairo_deserialize(&data, 0)?;
+        let token_id = id.to_bigint().to_string();
 
         let token = TokenInfo {
-            owner: owner_result,
-            value: project.slot.to_string(),
+            owner: owner.0.to_hex_string(),
+            value: unit_value.to_string(),
             account: "".to_string(),
             value_in_yielder: "0".to_string(),
             value_in_offsetter: "0".to_string(),
+            account_check: None,
         };
 
         slot_map.insert(token_id, token);
     }
 
-    for token_id in token_ids.iter() {
-        let owner = Felt::from_str(&slot_map[&token_id.to_bigint().to_string()].owner).unwrap();
-        let s1 = match provider
-            .call(
-                FunctionCall {
-                    contract_address: owner,
-                    entry_point_selector: selector!("supportsInterface"),
-                    calldata: vec![felt!(
-                        "0x2ceccef7f994940b3962a6c67e0ba4fcd37df7d131417c604f91e03caecc1cd"
-                    )],
-                },
-                BlockId::Tag(BlockTag::Latest),
-            )
-            .await
-        {
-            Ok(res) => res[0].to_bigint().to_string(),
-            Err(_) => "0".to_string(),
-        };
-        sleep(RATE_LIMIT).await; // Rate limit delay
+    check_enumeration(project, enumerated, excluded_count, &slot_map)?;
 
-        let s2 = match provider
-            .call(
-                FunctionCall {
-                    contract_address: owner,
-                    entry_point_selector: selector!("supportsInterface"),
-                    calldata: vec![felt!("0xa66bd575")],
-                },
-                BlockId::Tag(BlockTag::Latest),
-            )
-            .await
-        {
-            Ok(res) => res[0].to_bigint().to_string(),
-            Err(_) => "0".to_string(),
-        };
-        sleep(RATE_LIMIT).await; // Rate limit delay
-
-        let s3 = match provider
-            .call(
-                FunctionCall {
-                    contract_address: owner,
-                    entry_point_selector: selector!("supportsInterface"),
-                    calldata: vec![felt!("0xf10dbd44")],
-                },
-                BlockId::Tag(BlockTag::Latest),
-            )
-            .await
-        {
-            Ok(res) => res[0].to_bigint().to_string(),
-            Err(_) => "0".to_string(),
-        };
-        sleep(RATE_LIMIT).await; // Rate limit delay
-
-        let support = s1 == "1" || s2 == "1" || s3 == "1";
-        slot_map.insert(
-            token_id.to_bigint().to_string(),
-            TokenInfo {
-                owner: slot_map[&token_id.to_bigint().to_string()].owner.clone(),
-                value: slot_map[&token_id.to_bigint().to_string()].value.clone(),
-                account: support.to_string(),
-                value_in_yielder: "0".to_string(),
-                value_in_offsetter: "0".to_string(),
-            },
-        );
+    let owners: Vec<Felt> = token_ids
+        .iter()
+        .map(|id| Felt::from_str(&slot_map[&id.to_bigint().to_string()].owner).unwrap())
+        .collect();
+    println!("Fetching token account data...");
+    let accounts = check_accounts(provider, batcher, selectors, &owners, block_id).await?;
+    for (token_id, (support, account_check)) in token_ids.iter().zip(accounts) {
+        let token = slot_map.get_mut(&token_id.to_bigint().to_string()).unwrap();
+        token.account = support.to_string();
+        token.account_check = account_check;
     }
 
-    slot_tokens.insert(project.slot.to_string(), slot_map);
+    // v1 collections have no slots, tokens are keyed by unit value as in earlier snapshots
+    slot_tokens.insert(unit_value.to_string(), slot_map);
 
     Ok(slot_tokens)
 }
 
 async fn scan_slot_project(
-    provider: &JsonRpcClient<HttpTransport>,
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    selectors: &mut Selectors,
     project: &ProjectInfo,
+    exclusions: &mut Exclusions,
+    block_id: BlockId,
 ) -> Result<HashMap<String, HashMap<String, TokenInfo>>, Box<dyn std::error::Error>> {
     println!("\nScanning slot-based project: {}", project.name);
     let mut slot_tokens = HashMap::new();
 
     let mut slot_map = HashMap::new();
 
-    // Get token supply in current slot
-    let supply_result = provider
-        .call(
-            FunctionCall {
-                contract_address: project.address,
-                entry_point_selector: selector!("token_supply_in_slot"),
-                calldata: vec![Felt::from(project.slot), Felt::ZERO],
-            },
-            BlockId::Tag(BlockTag::Latest),
-        )
-        .await?;
-
-    sleep(RATE_LIMIT).await; // Rate limit delay
-
-    let slot_supply: usize = supply_result[0]
-        .to_bigint()
-        .to_string()
-        .parse()
-        .unwrap_or(0_usize);
-    println!("Slot {} supply: {}", project.slot, slot_supply);
-
-    // get token ids
-    let mut calls: Vec<FunctionCall> = vec![];
-    for index in 0..slot_supply {
-        let calldata = vec![
-            Felt::from(project.slot),
-            Felt::ZERO,
-            Felt::from(index),
-            Felt::ZERO,
-        ];
-
-        calls.push(FunctionCall {
-            contract_address: project.address,
-            entry_point_selector: selector!("token_in_slot_by_index"),
-            calldata,
-        });
-    }
-
-    println!("Fetching token ids...");
-    let token_ids = aggregate_calls(provider, calls).await?;
-
-    let token_ids: Vec<Felt> = token_ids[2..]
-        .chunks(3)
-        .map(|chunk| chunk[1].clone())
-        .filter(|id| {
-            !vec!["2055", "2056", "2057", "2059"].contains(&id.to_bigint().to_string().as_str())
-        })
-        .collect();
+    let reader = ProjectReader::new(project.address, provider);
+    let slot = U256::from_bytes_be(&project.slot.to_bytes_be());
 
-    let calls: Vec<FunctionCall> = token_ids
-        .iter()
-        .flat_map(|id| {
-            vec![
-                FunctionCall {
-                    contract_address: project.address,
-                    entry_point_selector: selector!("owner_of"),
-                    calldata: vec![*id, Felt::ZERO],
-                },
-                FunctionCall {
-                    contract_address: project.address,
-                    entry_point_selector: selector!("value_of"),
-                    calldata: vec![*id, Felt::ZERO],
-                },
-            ]
-        })
-        .collect();
+    let token_ids =
+        erc3525::slot_token_ids(provider, batcher, project.address, &slot, block_id).await?;
+    let slot_supply = token_ids.len();
+    let tokens =
+        erc3525::read_tokens(provider, batcher, project.address, &token_ids, block_id).await?;
 
-    println!("Fetching token data...");
-    let results = aggregate_calls(provider, calls).await?;
-
-    for (i, data) in results[2..].chunks(5).enumerate() {
-        let owner_result = data[1].to_hex_string();
-        let value_result = data[3].to_bigint().to_string();
-        let token_id = token_ids[i].to_bigint().to_string();
-
-        let token = TokenInfo {
-            owner: owner_result,
-            value: value_result,
+    let mut excluded_value: u128 = 0;
+    let mut excluded_count = 0;
+    let mut token_ids = vec![];
+    for token in tokens {
+        let id = u256_to_felt(&token.id);
+        if exclusions.exclude("v2", project, &id, token.value) {
+            excluded_value += token.value;
+            excluded_count += 1;
+            continue;
+        }
+        let info = TokenInfo {
+            owner: token.owner.to_hex_string(),
+            value: token.value.to_string(),
             account: "".to_string(),
             value_in_yielder: "0".to_string(),
             value_in_offsetter: "0".to_string(),
+            account_check: None,
         };
+        slot_map.insert(id.to_bigint().to_string(), info);
+        token_ids.push(id);
+    }
 
-        slot_map.insert(token_id, token);
+    check_enumeration(project, slot_supply, excluded_count, &slot_map)?;
+
+    // Scanned and excluded values must add up to the slot total value
+    let total_value = u256_to_u128(&reader.total_value(&slot).block_id(block_id).call().await?)?;
+    let scanned_value = slot_map
+        .values()
+        .map(|token| token.value.parse::<u128>().unwrap())
+        .sum::<u128>()
+        + excluded_value;
+    if scanned_value != total_value {
+        return Err(format!(
+            "{}: scanned value {} does not match slot {} total value {}",
+            project.name, scanned_value, project.slot, total_value
+        )
+        .into());
     }
 
-    let mut calls1: Vec<FunctionCall> = vec![];
-    let mut calls2: Vec<FunctionCall> = vec![];
-    let mut calls3: Vec<FunctionCall> = vec![];
     let mut calls4: Vec<FunctionCall> = vec![];
     let mut calls5: Vec<FunctionCall> = vec![];
+    let yielder = YielderReader::new(project.yielder, provider);
+    let offsetter = OffsetterReader::new(project.offsetter, provider);
 
-    for token_id in &token_ids {
-        let owner = Felt::from_str(&slot_map[&token_id.to_bigint().to_string()].owner).unwrap();
-
-        calls1.push(FunctionCall {
-            contract_address: owner,
-            entry_point_selector: selector!("supportsInterface"),
-            calldata: vec![felt!(
-                "0x2ceccef7f994940b3962a6c67e0ba4fcd37df7d131417c604f91e03caecc1cd"
-            )],
-        });
-        calls2.push(FunctionCall {
-            contract_address: owner,
-            entry_point_selector: selector!("supportsInterface"),
-            calldata: vec![felt!("0xa66bd575")],
-        });
-        calls3.push(FunctionCall {
-            contract_address: owner,
-            entry_point_selector: selector!("supportsInterface"),
-            calldata: vec![felt!("0xf10dbd44")],
-        });
-
-        calls4.push(FunctionCall {
-            contract_address: project.offsetter,
-            entry_point_selector: selector!("get_deposited_of"),
-            calldata: vec![owner],
-        });
-        calls5.push(FunctionCall {
-            contract_address: project.yielder,
-            entry_point_selector: selector!("get_deposited_of"),
-            calldata: vec![owner],
-        });
+    let owners: Vec<Felt> = token_ids
+        .iter()
+        .map(|id| Felt::from_str(&slot_map[&id.to_bigint().to_string()].owner).unwrap())
+        .collect();
+    for owner in &owners {
+        let owner = *owner;
+        calls4.push(offsetter.get_deposited_of(&ContractAddress(owner)).call_raw);
+        calls5.push(yielder.get_deposited_of(&ContractAddress(owner)).call_raw);
     }
 
     println!("Fetching token account data...");
-    let results1 = aggregate_calls(provider, calls1).await?;
-    let results2 = aggregate_calls(provider, calls2).await?;
-    let results3 = aggregate_calls(provider, calls3).await?;
+    let accounts = check_accounts(provider, batcher, selectors, &owners, block_id).await?;
 
     let results4 = if project.offsetter != felt!("0x0") {
         println!("Fetching token offsetter data...");
-        aggregate_calls(provider, calls4).await?
+        batcher.aggregate(provider, &calls4, block_id).await?
     } else {
         vec![]
     };
 
     let results5 = if project.yielder != felt!("0x0") {
         println!("Fetching token yielder data...");
-        aggregate_calls(provider, calls5).await?
+        batcher.aggregate(provider, &calls5, block_id).await?
     } else {
         vec![]
     };
 
-    for (i, token_id) in token_ids.iter().enumerate() {
+    for (i, (token_id, (support, account_check))) in token_ids.iter().zip(accounts).enumerate() {
         let token_id = &token_id.to_bigint().to_string();
-        let support_result = results1[2 + 2 * i + 1].to_bigint().to_string();
-        let support_result2 = results2[2 + 2 * i + 1].to_bigint().to_string();
-        let support_result3 = results3[2 + 2 * i + 1].to_bigint().to_string();
         let value_in_offsetter = if project.offsetter != felt!("0x0") {
-            results4[2 + 3 * i + 1].to_bigint().to_string()
+            u256_to_u128(&U256::cairo_deserialize(&results4[i], 0)?)?.to_string()
         } else {
             "0".to_string()
         };
         let value_in_yielder = if project.yielder != felt!("0x0") {
-            results5[2 + 3 * i + 1].to_bigint().to_string()
+            u256_to_u128(&U256::cairo_deserialize(&results5[i], 0)?)?.to_string()
         } else {
             "0".to_string()
         };
 
-        let support = support_result == "1" || support_result2 == "1" || support_result3 == "1";
         let token = TokenInfo {
             owner: slot_map[token_id].owner.clone(),
             value: slot_map[token_id].value.clone(),
             account: support.to_string(),
             value_in_yielder,
             value_in_offsetter,
+            account_check,
         };
 
         if !support {
@@ -397,51 +707,339 @@ async fn scan_slot_project(
     Ok(slot_tokens)
 }
 
+/// Gives each v2 project its slot: the slot of its yielder, or else the only slot
+/// of its contract. Fails unless every slot of the scanned contracts goes to
+/// exactly one project, so a new slot is never left out of the snapshot.
+async fn assign_slots(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    mut projects: Vec<ProjectInfo>,
+    block_id: BlockId,
+) -> Result<Vec<ProjectInfo>, Box<dyn std::error::Error>> {
+    let contracts: BTreeSet<Felt> = projects.iter().map(|project| project.address).collect();
+    let mut slots: HashMap<Felt, Vec<Felt>> = HashMap::new();
+    println!("\nDiscovering slots...");
+    for contract in contracts {
+        let contract_slots = erc3525::slots(provider, batcher, contract, block_id).await?;
+        slots.insert(contract, contract_slots.iter().map(u256_to_felt).collect());
+    }
+
+    for project in projects.iter_mut() {
+        let contract_slots = &slots[&project.address];
+        project.slot = if project.yielder != felt!("0x0") {
+            let yielder = YielderReader::new(project.yielder, provider);
+            let address = yielder
+                .get_carbonable_project_address()
+                .block_id(block_id)
+                .call()
+                .await?;
+            if address.0 != project.address {
+                return Err(format!(
+                    "{}: yielder {} is for project {}, not {}",
+                    project.name,
+                    project.yielder.to_hex_string(),
+                    address.0.to_hex_string(),
+                    project.address.to_hex_string()
+                )
+                .into());
+            }
+            let slot = yielder
+                .get_carbonable_project_slot()
+                .block_id(block_id)
+                .call()
+                .await?;
+            u256_to_felt(&slot)
+        } else {
+            match contract_slots.as_slice() {
+                [slot] => *slot,
+                _ => {
+                    return Err(format!(
+                        "{}: {} has {} slots, set a yielder to pick one",
+                        project.name,
+                        project.address.to_hex_string(),
+                        contract_slots.len()
+                    )
+                    .into())
+                }
+            }
+        };
+        if !contract_slots.contains(&project.slot) {
+            return Err(format!(
+                "{}: slot {} is not a slot of {}",
+                project.name,
+                project.slot,
+                project.address.to_hex_string()
+            )
+            .into());
+        }
+        println!("{}: slot {}", project.name, project.slot);
+    }
+
+    for (contract, contract_slots) in slots.iter() {
+        for slot in contract_slots {
+            let owners = projects
+                .iter()
+                .filter(|project| project.address == *contract && project.slot == *slot)
+                .count();
+            if owners != 1 {
+                return Err(format!(
+                    "slot {} of {} belongs to {} projects, expected one",
+                    slot,
+                    contract.to_hex_string(),
+                    owners
+                )
+                .into());
+            }
+        }
+    }
+    Ok(projects)
+}
+
+/// Reads claimable and claimed rewards for every depositor of the yielder, recording
+/// whether they add up to the yielder totals.
+async fn scan_yielder_rewards(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    project: &ProjectInfo,
+    block_id: BlockId,
+) -> Result<YielderRewards, Box<dyn std::error::Error>> {
+    println!("\nScanning yielder rewards: {}", project.name);
+    let depositors = yielder_depositors(provider, project.yielder, block_id).await?;
+    println!("Depositors: {}", depositors.len());
+
+    let yielder = YielderReader::new(project.yielder, provider);
+    let mut calls = vec![
+        yielder.get_total_claimable().call_raw,
+        yielder.get_total_claimed().call_raw,
+    ];
+    for depositor in depositors.iter() {
+        let account = ContractAddress(*depositor);
+        calls.push(yielder.get_claimable_of(&account).call_raw);
+        calls.push(yielder.get_claimed_of(&account).call_raw);
+    }
+
+    println!("Fetching rewards...");
+    let results = batcher.aggregate(provider, &calls, block_id).await?;
+    if results.len() != calls.len() {
+        return Err(format!(
+            "{}: {} reward calls returned {} results",
+            project.name,
+            calls.len(),
+            results.len()
+        )
+        .into());
+    }
+    // every result is a u256: [low, high]
+    let mut amounts: Vec<u128> = vec![];
+    for (call, data) in calls.iter().zip(&results) {
+        if data.len() != 2 {
+            return Err(format!(
+                "{}: {} returned {} felts, expected a u256",
+                project.name,
+                call.entry_point_selector.to_hex_string(),
+                data.len()
+            )
+            .into());
+        }
+        amounts.push(u256_to_u128(&U256::cairo_deserialize(data, 0)?)?);
+    }
+
+    let (total_claimable, total_claimed) = (amounts[0], amounts[1]);
+    let mut claimable_sum = 0;
+    let mut claimed_sum = 0;
+    let mut rewards = HashMap::new();
+    for (depositor, data) in depositors.iter().zip(amounts[2..].chunks_exact(2)) {
+        claimable_sum += data[0];
+        claimed_sum += data[1];
+        if data[0] == 0 && data[1] == 0 {
+            continue;
+        }
+        rewards.insert(
+            depositor.to_hex_string(),
+            RewardInfo {
+                claimable: data[0].to_string(),
+                claimed: data[1].to_string(),
+            },
+        );
+    }
+
+    println!(
+        "Claimable for {} is ${} out of ${}, claimed ${} out of ${}",
+        project.name,
+        claimable_sum / 1_000_000,
+        total_claimable / 1_000_000,
+        claimed_sum / 1_000_000,
+        total_claimed / 1_000_000
+    );
+
+    Ok(YielderRewards {
+        yielder: project.yielder.to_hex_string(),
+        total_claimable: total_claimable.to_string(),
+        total_claimed: total_claimed.to_string(),
+        claimable_sum: claimable_sum.to_string(),
+        claimed_sum: claimed_sum.to_string(),
+        consistent: claimable_sum == total_claimable && claimed_sum == total_claimed,
+        depositors: rewards,
+    })
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting Starknet slot-based token scanner...");
     let start_time = Instant::now();
 
-    let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(
-        "https://rpc.starknet.lava.build:443",
-    )?));
+    let url = Url::parse("https://rpc.starknet.lava.build:443")?;
+    let provider = JsonRpcClient::new(CachedTransport::new(HttpTransport::new(url.clone())).await?);
+    let batcher = Batcher::from_env(CachedTransport::new(HttpTransport::new(url)).await?)?;
     println!("Provider initialized successfully");
 
+    let publisher = Publisher::from_env("SCAN_SUBJECT_PREFIX", "snapshot").await?;
+    let mut selectors = Selectors::default();
+    let mut exclusions = Exclusions {
+        rules: load_exclusions()?,
+        excluded: vec![],
+    };
+
+    // Pin every call to the same block so the snapshot is consistent,
+    // `SNAPSHOT_BLOCK` reruns at a past block and hits the call cache
+    let block = match std::env::var("SNAPSHOT_BLOCK") {
+        Ok(block_number) => {
+            let block_number: u64 = block_number.parse()?;
+            match provider
+                .get_block_with_tx_hashes(BlockId::Number(block_number))
+                .await?
+            {
+                MaybePendingBlockWithTxHashes::Block(block) => BlockHashAndNumber {
+                    block_hash: block.block_hash,
+                    block_number,
+                },
+                MaybePendingBlockWithTxHashes::PendingBlock(_) => {
+                    return Err(format!("block {} is pending", block_number).into())
+                }
+            }
+        }
+        Err(_) => provider.block_hash_and_number().await?,
+    };
+    let block_id = BlockId::Number(block.block_number);
+    println!("Snapshot at block {}", block.block_number);
+    publisher
+        .publish(
+            "started",
+            json!({
+                "block_number": block.block_number,
+                "block_hash": block.block_hash.to_hex_string(),
+            }),
+        )
+        .await;
+
+    let mut totals = HashMap::new();
+    // Collection metadata of every scanned project
+    let mut headers = HashMap::new();
+    // Every scanned contract and slot, hashed into the snapshot metadata
+    let mut config = vec![];
+
+    // v2 slots are discovered on chain, see `assign_slots`
     let project_addresses = vec![
         ProjectInfo {
             name: "BanegasFarm".to_string(),
-            slot: felt!("0x1"),
-            address: felt!("0x0516d0acb6341dcc567e85dc90c8f64e0c33d3daba0a310157d6bba0656c8769"),
-            yielder: felt!("0x03d25473be5a6316f351e8f964d0c303357c006f7107779f648d9879b7c6d58a"),
-            offsetter: felt!("0x0324b531f731100b494e2f978a26b20b5870585dd96d9f1166b43a28ebbb8aba"),
+            slot: Felt::ZERO,
+            address: PROJECT_V2,
+            yielder: BANEGAS_YIELDER,
+            offsetter: BANEGAS_OFFSETTER,
         },
         ProjectInfo {
             name: "LasDelicias".to_string(),
-            slot: felt!("0x2"),
-            address: felt!("0x0516d0acb6341dcc567e85dc90c8f64e0c33d3daba0a310157d6bba0656c8769"),
-            yielder: felt!("0x00426d4e86913759bcc49b7f992b1fe62e6571e8f8089c23d95fea815dbad471"),
-            offsetter: felt!("0x022f40128af9798a0b734874fd993bbab6cf75845f26f844cb151b7041132c6d"),
+            slot: Felt::ZERO,
+            address: PROJECT_V2,
+            yielder: LAS_DELICIAS_YIELDER,
+            offsetter: LAS_DELICIAS_OFFSETTER,
         },
         ProjectInfo {
             name: "Manjarisoa".to_string(),
-            slot: felt!("0x3"),
-            address: felt!("0x0516d0acb6341dcc567e85dc90c8f64e0c33d3daba0a310157d6bba0656c8769"),
-            yielder: felt!("0x03afe61732ed9b226309775ac4705129319729d3bee81da5632146ffd72652ae"),
-            offsetter: felt!("0x04258037980fcc15083cde324abe1861ac00d4d48b7d60d76b5efd6f57e59e73"),
+            slot: Felt::ZERO,
+            address: PROJECT_V2,
+            yielder: MANJARISOA_YIELDER,
+            offsetter: MANJARISOA_OFFSETTER,
         },
         ProjectInfo {
             name: "Karathuru".to_string(),
-            slot: felt!("0x1"),
-            address: felt!("0x05a667adc04676fba78a29371561a0bf91dab25847d5dc4709a93a4cfb5ff293"),
+            slot: Felt::ZERO,
+            address: KARATHURU_V2,
             yielder: felt!("0x0"),
             offsetter: felt!("0x0"),
         },
     ];
 
     let mut results = HashMap::new();
+    let mut rewards = HashMap::new();
 
+    let project_addresses = assign_slots(&provider, &batcher, project_addresses, block_id).await?;
     for project in project_addresses {
-        let token_data = scan_slot_project(&provider, &project).await?;
+        publisher
+            .publish(
+                "progress",
+                json!({ "version": "v2", "project": project.name }),
+            )
+            .await;
+        config.extend([
+            project.address,
+            project.slot,
+            project.yielder,
+            project.offsetter,
+        ]);
+        let token_data = scan_slot_project(
+            &provider,
+            &batcher,
+            &mut selectors,
+            &project,
+            &mut exclusions,
+            block_id,
+        )
+        .await?;
+        publish_project(&publisher, "v2", &project, &token_data).await;
+        totals.insert(project.name.clone() + "v2", project_totals(&token_data));
+        let slot_uri = ("slot_uri", vec![project.slot, Felt::ZERO]);
+        headers.insert(
+            project.name.clone() + "v2",
+            read_metadata(&provider, &mut selectors, &project, slot_uri, block_id).await,
+        );
+        if project.yielder != felt!("0x0") {
+            let project_rewards =
+                scan_yielder_rewards(&provider, &batcher, &project, block_id).await?;
+            if !project_rewards.consistent {
+                println!(
+                    "{}: depositor rewards (claimable {}, claimed {}) do not match yielder totals (claimable {}, claimed {})",
+                    project.name,
+                    project_rewards.claimable_sum,
+                    project_rewards.claimed_sum,
+                    project_rewards.total_claimable,
+                    project_rewards.total_claimed
+                );
+                publisher
+                    .publish(
+                        "verification.failed",
+                        json!({
+                            "version": "v2",
+                            "project": project.name,
+                            "reason": "depositor rewards do not match the yielder totals",
+                            "claimable_sum": project_rewards.claimable_sum,
+                            "claimed_sum": project_rewards.claimed_sum,
+                            "total_claimable": project_rewards.total_claimable,
+                            "total_claimed": project_rewards.total_claimed,
+                        }),
+                    )
+                    .await;
+            }
+            totals.insert(
+                project.name.clone() + "rewards",
+                json!({
+                    "claimable": project_rewards.total_claimable,
+                    "claimed": project_rewards.total_claimed,
+                    "consistent": project_rewards.consistent,
+                }),
+            );
+            rewards.insert(project.name.clone(), project_rewards);
+        }
         results.insert(project.name, token_data);
     }
 
@@ -453,50 +1051,144 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "output/sv2.json",
         serde_json::to_string_pretty(&json_output)?,
     )?;
+    std::fs::write(
+        "output/rewards.json",
+        serde_json::to_string_pretty(&rewards)?,
+    )?;
 
     // v1 projects
+    let mut sales = HashMap::new();
     let project_addresses = vec![
-        ProjectInfo {
-            name: "BanegasFarm".to_string(),
-            slot: Felt::from(110000000),
-            address: BANEGAS,
-            yielder: felt!("0x0"),
-            offsetter: felt!("0x0"),
-        },
-        ProjectInfo {
-            name: "LasDelicias".to_string(),
-            slot: Felt::from(110000000),
-            address: LAS_DELICIAS,
-            yielder: felt!("0x0"),
-            offsetter: felt!("0x0"),
-        },
-        ProjectInfo {
-            name: "ManjarisoBronze".to_string(),
-            slot: Felt::from(54500000),
-            address: MANJARISO_BRONZE,
-            yielder: felt!("0x0"),
-            offsetter: felt!("0x0"),
-        },
-        ProjectInfo {
-            name: "ManjarisoSilver".to_string(),
-            slot: Felt::from(272500000),
-            address: MANJARISO_SILVER,
-            yielder: felt!("0x0"),
-            offsetter: felt!("0x0"),
-        },
-        ProjectInfo {
-            name: "ManjarisoGold".to_string(),
-            slot: Felt::from(817500000),
-            address: MANJARISO_GOLD,
-            yielder: felt!("0x0"),
-            offsetter: felt!("0x0"),
-        },
+        (
+            ProjectInfo {
+                name: "BanegasFarm".to_string(),
+                slot: Felt::ZERO,
+                address: BANEGAS_V1,
+                yielder: felt!("0x0"),
+                offsetter: felt!("0x0"),
+            },
+            V1Pricing {
+                minter: None,
+                unit_price: 110000000,
+            },
+        ),
+        (
+            ProjectInfo {
+                name: "LasDelicias".to_string(),
+                slot: Felt::ZERO,
+                address: LAS_DELICIAS_V1,
+                yielder: felt!("0x0"),
+                offsetter: felt!("0x0"),
+            },
+            V1Pricing {
+                minter: None,
+                unit_price: 110000000,
+            },
+        ),
+        (
+            ProjectInfo {
+                name: "ManjarisoBronze".to_string(),
+                slot: Felt::ZERO,
+                address: MANJARISO_BRONZE_V1,
+                yielder: felt!("0x0"),
+                offsetter: felt!("0x0"),
+            },
+            V1Pricing {
+                minter: None,
+                unit_price: 54500000,
+            },
+        ),
+        (
+            ProjectInfo {
+                name: "ManjarisoSilver".to_string(),
+                slot: Felt::ZERO,
+                address: MANJARISO_SILVER_V1,
+                yielder: felt!("0x0"),
+                offsetter: felt!("0x0"),
+            },
+            V1Pricing {
+                minter: None,
+                unit_price: 272500000,
+            },
+        ),
+        (
+            ProjectInfo {
+                name: "ManjarisoGold".to_string(),
+                slot: Felt::ZERO,
+                address: MANJARISO_GOLD_V1,
+                yielder: felt!("0x0"),
+                offsetter: felt!("0x0"),
+            },
+            V1Pricing {
+                minter: None,
+                unit_price: 817500000,
+            },
+        ),
     ];
 
     let mut results = HashMap::new();
 
-    for project in project_addresses {
-        let token_data = scan_project(&provider, &project).await?;
+    for (project, pricing) in project_addresses {
+        publisher
+            .publish(
+                "progress",
+                json!({ "version": "v1", "project": project.name }),
+            )
+            .await;
+        config.extend([
+            project.address,
+            project.slot,
+            project.yielder,
+            project.offsetter,
+            Felt::from(pricing.unit_price),
+            pricing.minter.unwrap_or(Felt::ZERO),
+        ]);
+        let project_sales = read_minter_sales(&provider, &project, &pricing, block_id).await?;
+        if !project_sales.consistent {
+            println!(
+                "{}: {} tokens sold at {} but the minter received {}",
+                project.name,
+                project_sales.sold_tokens,
+                project_sales.unit_price,
+                project_sales.sold_value
+            );
+            publisher
+                .publish(
+                    "verification.failed",
+                    json!({
+                        "version": "v1",
+                        "project": project.name,
+                        "reason": "minter sales do not match the unit price",
+                        "sales": project_sales,
+                    }),
+                )
+                .await;
+        }
+        let token_data = scan_project(
+            &provider,
+            &batcher,
+            &mut selectors,
+            &project,
+            pricing.unit_price,
+            &mut exclusions,
+            block_id,
+        )
+        .await?;
+        publish_project(&publisher, "v1", &project, &token_data).await;
+        totals.insert(project.name.clone() + "v1", project_totals(&token_data));
+        // v1 URIs are per token, the lowest scanned token stands for the collection
+        let first_token = token_data
+            .values()
+            .flat_map(|slot_map| slot_map.keys())
+            .filter_map(|id| Felt::from_dec_str(id).ok())
+            .min()
+            .unwrap_or(Felt::ONE);
+        let token_uri = ("tokenURI", vec![first_token, Felt::ZERO]);
+        headers.insert(
+            project.name.clone() + "v1",
+            read_metadata(&provider, &mut selectors, &project, token_uri, block_id).await,
+        );
+        sales.insert(project.name.clone() + "v1", project_sales);
         results.insert(project.name, token_data);
     }
 
@@ -509,18 +1201,140 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         serde_json::to_string_pretty(&json_output)?,
     )?;
 
+    println!("\nExcluded tokens:");
+    let mut excluded_value: HashMap<String, u128> = HashMap::new();
+    for token in exclusions.excluded.iter() {
+        println!(
+            "{} {} token {} (${}): {}",
+            token.project,
+            token.version,
+            token.token_id,
+            token.value.parse::<u128>().unwrap_or(0) / 1_000_000,
+            token.reason
+        );
+        if token.unconfirmed {
+            println!("  Reason unconfirmed, see \"Exclusions to confirm\" in to-check.md");
+            publisher
+                .publish(
+                    "verification.failed",
+                    json!({
+                        "version": token.version,
+                        "project": token.project,
+                        "token_id": token.token_id,
+                        "value": token.value,
+                        "reason": "excluded by an unconfirmed rule",
+                    }),
+                )
+                .await;
+        }
+        *excluded_value
+            .entry(token.project.clone() + &token.version)
+            .or_default() += token.value.parse::<u128>().unwrap_or(0);
+    }
+    for (project, value) in excluded_value.iter() {
+        println!("Value excluded from {}: ${}", project, value / 1_000_000);
+    }
+    std::fs::write(
+        "output/exclusions.json",
+        serde_json::to_string_pretty(&exclusions.excluded)?,
+    )?;
+
+    let metadata = json!({
+        "block_number": block.block_number,
+        "block_hash": block.block_hash.to_hex_string(),
+        "config_hash": compute_hash_on_elements(&config).to_hex_string(),
+        "timestamp": std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+        "totals": totals,
+        "projects": headers,
+        "sales": sales,
+    });
+    std::fs::write("output/meta.json", serde_json::to_string_pretty(&metadata)?)?;
+    // Owner aggregates are added to the same run by `total`
+    store::record_if_requested(store::record_tokens)?;
+    publisher.publish("completed", metadata).await;
+    publisher.flush().await;
+
     let duration = start_time.elapsed();
     println!("\nScan completed in {:?}", duration);
 
     Ok(())
 }
 
-const BANEGAS: Felt = felt!("0x04047810e4f759336f941a16b6de9d8d2f934e976b9a9431a2964646df9025c6");
-const LAS_DELICIAS: Felt =
-    felt!("0x00ebf4bbab9c934fa0212c9358331d4a9543ad66a7e0007d4a720cfa6b56061e");
-const MANJARISO_BRONZE: Felt =
-    felt!("0x0541b5dd5fae206ceccaf4eeb0642e4c04d456c5bc296eab047c9414bdad4f09");
-const MANJARISO_SILVER: Felt =
-    felt!("0x06191013bbd6bcf11d69f3d60d20f7aa03439d7011bd511d13122de2275dce21");
-const MANJARISO_GOLD: Felt =
-    felt!("0x061bcc33b0469cd072ad47813a1efd250fd36a28425d774c31c8f33c87306e8e");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn project() -> ProjectInfo {
+        ProjectInfo {
+            name: "BanegasFarm".to_string(),
+            slot: Felt::ONE,
+            address: Felt::TWO,
+            yielder: Felt::ZERO,
+            offsetter: Felt::ZERO,
+        }
+    }
+
+    #[test]
+    fn exclusions_record_the_rule_reason() {
+        let rules = serde_json::from_value(json!([
+            { "kind": "slot", "contract": "0x2", "slot": "1", "reason": "test slot", "unconfirmed": true },
+        ]))
+        .unwrap();
+        let mut exclusions = Exclusions {
+            rules,
+            excluded: vec![],
+        };
+        assert!(!exclusions.exclude("v1", &project(), &Felt::from(7), 100));
+        assert!(exclusions.exclude("v2", &project(), &Felt::from(7), 100));
+
+        let [token] = exclusions.excluded.as_slice() else {
+            panic!("one token should be excluded");
+        };
+        assert_eq!(
+            (token.token_id.as_str(), token.value.as_str()),
+            ("7", "100")
+        );
+        assert_eq!(token.reason, "test slot");
+        assert!(token.unconfirmed);
+    }
+
+    #[test]
+    fn account_checks_keep_the_first_failure() {
+        let yes = Ok(vec![Felt::ONE]);
+        let no = Ok(vec![Felt::ZERO]);
+        let not_found = Err(CallFailure::EntrypointNotFound);
+        let unanswered = Err(CallFailure::Unanswered);
+
+        assert_eq!(
+            account_check(&[no.clone(), yes, not_found.clone()]),
+            (true, None)
+        );
+        assert_eq!(account_check(&[no, not_found.clone()]), (false, None));
+        assert_eq!(
+            account_check(&[unanswered, not_found]),
+            (false, Some(CallFailure::Unanswered))
+        );
+    }
+
+    #[test]
+    fn enumeration_counts_held_and_excluded_tokens() {
+        let token = |owner: Felt| TokenInfo {
+            owner: owner.to_hex_string(),
+            value: "1".to_string(),
+            account: "".to_string(),
+            value_in_yielder: "0".to_string(),
+            value_in_offsetter: "0".to_string(),
+            account_check: None,
+        };
+        let holders = HashMap::from([
+            ("1".to_string(), token(Felt::THREE)),
+            ("2".to_string(), token(Felt::ZERO)),
+        ]);
+        assert!(check_enumeration(&project(), 2, 1, &holders).is_ok());
+        // A zero owner does not count as held
+        assert!(check_enumeration(&project(), 2, 0, &holders).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use futures::StreamExt;
+use rust_playground::model::{self, normalize, Snapshot};
+use serde_json::{json, Value};
+use std::{path::Path, time::SystemTime};
+
+/// Files and directories the store is loaded from.
+const INPUTS: [&str; 4] = ["output/meta.json", "snapshot", "tonnes", "adjustments.json"];
+
+/// Latest modification time of the inputs, or of the files of an input directory.
+fn inputs_modified() -> Option<SystemTime> {
+    let mut latest = None;
+    for input in INPUTS {
+        let path = Path::new(input);
+        let mut paths = vec![path.to_path_buf()];
+        if let Ok(entries) = std::fs::read_dir(path) {
+            paths.extend(entries.flatten().map(|entry| entry.path()));
+        }
+        for path in paths {
+            if let Ok(modified) = std::fs::metadata(&path).and_then(|m| m.modified()) {
+                latest = latest.max(Some(modified));
+            }
+        }
+    }
+    latest
+}
+
+/// Snapshot as last loaded, or why loading it failed.
+struct Loaded {
+    store: Result<Snapshot, String>,
+    modified: Option<SystemTime>,
+}
+
+impl Loaded {
+    fn load() -> Self {
+        let modified = inputs_modified();
+        let store = model::load().map_err(|e| e.to_string());
+        match &store {
+            Ok(store) => println!(
+                "Loaded {} owners across {} projects",
+                store.owners.len(),
+                store.projects.len()
+            ),
+            Err(e) => eprintln!("Error loading the snapshot: {}", e),
+        }
+        Loaded { store, modified }
+    }
+
+    /// Loads the files again when one of them changed since the last load.
+    fn refresh(&mut self) {
+        if inputs_modified() != self.modified {
+            *self = Loaded::load();
+        }
+    }
+}
+
+/// Answers `<prefix>.owner.<address>`, `<prefix>.project.<name>.totals` and `<prefix>.meta`.
+fn handle(store: &Snapshot, prefix: &str, subject: &str) -> Value {
+    let Some(query) = subject
+        .strip_prefix(prefix)
+        .and_then(|s| s.strip_prefix('.'))
+    else {
+        return json!({ "error": "unknown subject" });
+    };
+    let parts: Vec<&str> = query.split('.').collect();
+
+    match parts.as_slice() {
+        ["meta"] => store.metadata.clone(),
+        ["owner", address] => {
+            let address = match normalize(address) {
+                Ok(address) => address,
+                Err(e) => return json!({ "error": e }),
+            };
+            match store.owners.get(&address) {
+                Some(projects) => json!({ "owner": address, "projects": projects }),
+                None => json!({ "error": "owner not found" }),
+            }
+        }
+        ["project", name, "totals"] => match store.projects.get(*name) {
+            Some(totals) => json!({ "project": name, "totals": totals }),
+            None => json!({ "error": "project not found" }),
+        },
+        _ => json!({ "error": "unknown subject" }),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let url = std::env::var("NATS_URL").unwrap_or("nats://127.0.0.1:4222".to_string());
+    let prefix = std::env::var("SERVE_SUBJECT_PREFIX").unwrap_or("snapshot".to_string());
+
+    let mut loaded = Loaded::load();
+    if let Err(e) = &loaded.store {
+        return Err(e.clone().into());
+    }
+
+    let client = async_nats::connect(url).await?;
+    let mut subscriber = client.subscribe(prefix.clone() + ".>").await?;
+    println!("Serving requests on {}.>", prefix);
+
+    while let Some(message) = subscriber.next().await {
+        // Events published by `scan` share the prefix but expect no reply
+        let Some(reply) = message.reply else {
+            continue;
+        };
+        loaded.refresh();
+        let response = match &loaded.store {
+            Ok(store) => handle(store, &prefix, message.subject.as_str()),
+            Err(e) => json!({ "error": format!("snapshot files could not be loaded: {}", e) }),
+        };
+        if let Err(e) = client.publish(reply, response.to_string().into()).await {
+            eprintln!("Error replying to {}: {}", message.subject, e);
+        }
+    }
+
+    Ok(())
+}
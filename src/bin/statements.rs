@@ -0,0 +1,420 @@
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Document, Object, Stream,
+};
+use rust_playground::{
+    allocation::load_allocation,
+    model::{load_adjustments, read_json, Adjustment, PROJECTS},
+};
+use serde_json::Value;
+use starknet::core::{crypto::compute_hash_on_elements, types::Felt};
+use std::collections::BTreeMap;
+
+pub const LINES_PER_PAGE: usize = 64;
+
+#[derive(Debug)]
+struct TokenLine {
+    project: String,
+    version: String,
+    token_id: String,
+    value: u128,
+}
+
+#[derive(Debug, Default)]
+struct Deposits {
+    yielder: u128,
+    offsetter: u128,
+}
+
+#[derive(Debug, Default)]
+struct Statement {
+    tokens: Vec<TokenLine>,
+    deposits: BTreeMap<String, Deposits>,
+    adjustments: Vec<Adjustment>,
+    tonnes: BTreeMap<String, u128>,
+}
+
+/// JSON of `path`, failing when it is missing or empty.
+fn read_required(path: &str) -> Result<Value, Box<dyn std::error::Error>> {
+    Ok(read_json(path)?.ok_or(format!("{} is missing or empty", path))?)
+}
+
+fn signed_usd(value: i64) -> String {
+    let sign = if value < 0 { "-" } else { "+" };
+    sign.to_owned() + &usd(value.unsigned_abs() as u128)
+}
+
+fn usd(value: u128) -> String {
+    format!("${}.{:02}", value / 1_000_000, (value % 1_000_000) / 10_000)
+}
+
+fn tonnes(amount: u128) -> String {
+    format!(
+        "{}.{:03}t",
+        amount / 1_000_000_000,
+        (amount % 1_000_000_000) / 1_000_000
+    )
+}
+
+/// Writes `lines` as a plain text PDF, paginated every `LINES_PER_PAGE` lines.
+fn write_pdf(path: &str, lines: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Courier",
+    });
+    let resources_id = doc.add_object(dictionary! {
+        "Font" => dictionary! {
+            "F1" => font_id,
+        },
+    });
+
+    let mut kids: Vec<Object> = vec![];
+    for page in lines.chunks(LINES_PER_PAGE) {
+        let mut operations = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), 9.into()]),
+            Operation::new("TL", vec![12.into()]),
+            Operation::new("Td", vec![40.into(), 800.into()]),
+        ];
+        for line in page {
+            operations.push(Operation::new(
+                "Tj",
+                vec![Object::string_literal(line.as_str())],
+            ));
+            operations.push(Operation::new("T*", vec![]));
+        }
+        operations.push(Operation::new("ET", vec![]));
+
+        let content = Content { operations };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode()?));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        kids.push(page_id.into());
+    }
+
+    let pages = dictionary! {
+        "Type" => "Pages",
+        "Count" => kids.len() as u32,
+        "Kids" => kids,
+        "Resources" => resources_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+    };
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    doc.compress();
+    doc.save(path)?;
+    Ok(())
+}
+
+fn load_statements() -> Result<BTreeMap<String, Statement>, Box<dyn std::error::Error>> {
+    let mut statements: BTreeMap<String, Statement> = BTreeMap::new();
+
+    for version in ["v1", "v2"] {
+        let json = read_required(&("output/s".to_owned() + version + ".json"))?;
+        for (project, slots) in json.as_object().unwrap() {
+            for (_, tokens) in slots.as_object().unwrap() {
+                for (token_id, token_info) in tokens.as_object().unwrap() {
+                    let owner = Felt::from_hex(token_info["owner"].as_str().unwrap())
+                        .unwrap()
+                        .to_hex_string();
+                    let parse =
+                        |key: &str| -> u128 { token_info[key].as_str().unwrap().parse().unwrap() };
+                    let statement = statements.entry(owner).or_default();
+                    statement.tokens.push(TokenLine {
+                        project: project.clone(),
+                        version: version.to_string(),
+                        token_id: token_id.clone(),
+                        value: parse("value"),
+                    });
+                    // Farming deposits are per owner, repeated on each of their tokens
+                    let deposits = statement.deposits.entry(project.clone()).or_default();
+                    deposits.yielder = parse("value_in_yielder");
+                    deposits.offsetter = parse("value_in_offsetter");
+                }
+            }
+        }
+    }
+
+    for adjustment in load_adjustments()? {
+        statements
+            .entry(adjustment.owner.to_hex_string())
+            .or_default()
+            .adjustments
+            .push(adjustment);
+    }
+
+    // Owners listed twice in `tonnes/` (padded and unpadded addresses) are summed
+    for (project, _) in PROJECTS {
+        for (owner, amount) in load_allocation(project)? {
+            statements
+                .entry(owner.to_hex_string())
+                .or_default()
+                .tonnes
+                .insert(project.to_string(), amount);
+        }
+    }
+
+    Ok(statements)
+}
+
+fn statement_lines(owner: &str, statement: &Statement) -> Vec<String> {
+    let mut lines = vec![
+        "Carbonable migration statement".to_string(),
+        format!("Owner: {}", owner),
+        "".to_string(),
+        "Tokens".to_string(),
+    ];
+    for token in &statement.tokens {
+        lines.push(format!(
+            "  {:<16} {}  #{:<8} {:>14}",
+            token.project,
+            token.version,
+            token.token_id,
+            usd(token.value)
+        ));
+    }
+
+    lines.push("".to_string());
+    lines.push("Farming deposits".to_string());
+    for (project, deposits) in &statement.deposits {
+        if deposits.yielder == 0 && deposits.offsetter == 0 {
+            continue;
+        }
+        lines.push(format!(
+            "  {:<16} yielder {:>14}  offsetter {:>14}",
+            project,
+            usd(deposits.yielder),
+            usd(deposits.offsetter)
+        ));
+    }
+
+    if !statement.adjustments.is_empty() {
+        lines.push("".to_string());
+        lines.push("Manual adjustments".to_string());
+        for adjustment in &statement.adjustments {
+            lines.push(format!(
+                "  {:<16} {:>15}  {} by {}",
+                adjustment.project,
+                signed_usd(adjustment.amount),
+                adjustment.date,
+                adjustment.author
+            ));
+            lines.push(format!("    {}", adjustment.justification));
+        }
+    }
+
+    lines.push("".to_string());
+    lines.push("Migration entitlement".to_string());
+    let mut elements = vec![Felt::from_hex(owner).unwrap()];
+    for (project, amount) in &statement.tonnes {
+        lines.push(format!("  {:<16} {:>18}", project, tonnes(*amount)));
+        elements.push(Felt::from(*amount));
+    }
+
+    // Recomputable from `tonnes/*.json`, so holders can check the document
+    lines.push("".to_string());
+    lines.push(format!(
+        "Entitlement fingerprint: {}",
+        compute_hash_on_elements(&elements).to_hex_string()
+    ));
+    lines
+}
+
+/// Token of `output/s<version>.json` whose owner did not answer as an account.
+#[derive(Debug)]
+struct NonAccountToken {
+    project: String,
+    token_id: String,
+    owner: String,
+    value: u128,
+    /// `account_check` of the scan, when it classified the failure
+    why: String,
+}
+
+fn non_account_tokens(version: &str) -> Result<Vec<NonAccountToken>, Box<dyn std::error::Error>> {
+    let json = read_required(&("output/s".to_owned() + version + ".json"))?;
+    let mut tokens = vec![];
+    for (project, slots) in json.as_object().unwrap() {
+        for (_, slot_tokens) in slots.as_object().unwrap() {
+            for (token_id, info) in slot_tokens.as_object().unwrap() {
+                if info["account"] != "false" {
+                    continue;
+                }
+                let owner = Felt::from_hex(info["owner"].as_str().unwrap()).unwrap();
+                tokens.push(NonAccountToken {
+                    project: project.clone(),
+                    token_id: token_id.clone(),
+                    owner: owner.to_hex_string(),
+                    value: info["value"].as_str().unwrap().parse().unwrap(),
+                    why: info["account_check"]
+                        .as_str()
+                        .unwrap_or("not an account")
+                        .to_string(),
+                });
+            }
+        }
+    }
+    tokens.sort_by(|a, b| (&a.project, &a.owner).cmp(&(&b.project, &b.owner)));
+    Ok(tokens)
+}
+
+/// Reproduces the `to-check.md` review: non-account holders, manual adjustments and,
+/// per project, the registry totals against what the snapshots and allocation hold.
+fn reconciliation_lines() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut lines = vec![
+        "Carbonable migration reconciliation".to_string(),
+        "".to_string(),
+    ];
+
+    // Owners removed by `tonnes`, so contracts already handled are not flagged again
+    let excluded = read_required("output/allocation_exclusions.json")?;
+    let excluded: BTreeMap<String, String> = excluded
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            let owner = Felt::from_hex(e["owner"].as_str().unwrap()).unwrap();
+            (
+                owner.to_hex_string(),
+                e["reason"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+
+    lines.push("Undeployed addresses".to_string());
+    for version in ["v1", "v2"] {
+        for token in non_account_tokens(version)? {
+            let status = match excluded.get(&token.owner) {
+                Some(reason) => format!("excluded: {}", reason),
+                None => token.why,
+            };
+            lines.push(format!(
+                "  {} {:<16} #{:<6} {} {:>14}  {}",
+                version,
+                token.project,
+                token.token_id,
+                token.owner,
+                usd(token.value),
+                status
+            ));
+        }
+    }
+    lines.push("".to_string());
+
+    let adjustments = load_adjustments()?;
+    lines.push("Manual adjustments".to_string());
+    for adjustment in &adjustments {
+        lines.push(format!(
+            "  {:<12} {} {:>15}  {} by {}",
+            adjustment.project,
+            adjustment.owner.to_hex_string(),
+            signed_usd(adjustment.amount),
+            adjustment.date,
+            adjustment.author
+        ));
+        lines.push(format!("    {}", adjustment.justification));
+    }
+    lines.push("".to_string());
+
+    // Written by `tonnes`, one summary per project
+    let summaries = read_required("output/allocation.json")?;
+    lines.push("Totals per project".to_string());
+    for (project, snapshots) in PROJECTS {
+        let summary = summaries
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["project"] == project)
+            .ok_or(format!("output/allocation.json has no {}", project))?;
+        let total_value: u128 = summary["total_value"].as_u64().unwrap().into();
+        let total_tonnes: u128 = summary["total_tonnes"].as_u64().unwrap().into();
+        let value_handled: u128 = summary["value_handled"].as_u64().unwrap().into();
+
+        lines.push(format!("{}:", project));
+        lines.push("  Actual".to_string());
+        lines.push(format!("    Project value: {}", usd(total_value)));
+        lines.push(format!("    Total carbon: {}", tonnes(total_tonnes)));
+
+        lines.push("  Snapshot".to_string());
+        let mut snapshot_value = 0;
+        for snapshot in snapshots {
+            let Some(data) = read_json("snapshot/".to_owned() + snapshot + ".json")? else {
+                continue;
+            };
+            let mut value = 0;
+            let mut value_offset = 0;
+            let mut value_yielder = 0;
+            for (_, info) in data.as_object().unwrap() {
+                value += info["value"].as_u64().unwrap() as u128;
+                value_offset += info["value_offset"].as_u64().unwrap() as u128;
+                value_yielder += info["value_yielder"].as_u64().unwrap() as u128;
+            }
+            // Deposits are held by the farming contracts, already counted in `value`
+            snapshot_value += value;
+            lines.push(format!(
+                "    {:<20} value {:>14}  yielder {:>14}  offsetter {:>14}",
+                snapshot,
+                usd(value),
+                usd(value_yielder),
+                usd(value_offset)
+            ));
+        }
+        let adjusted: i64 = adjustments
+            .iter()
+            .filter(|a| a.project == project)
+            .map(|a| a.amount)
+            .sum();
+        let allocation = load_allocation(project)?;
+        lines.push(format!("    Total snapshot value: {}", usd(snapshot_value)));
+        lines.push(format!(
+            "    Excluded owners: {}",
+            usd(summary["excluded_value"].as_u64().unwrap().into())
+        ));
+        lines.push(format!("    Manual adjustments: {}", signed_usd(adjusted)));
+        lines.push(format!("    Allocated value: {}", usd(value_handled)));
+        lines.push(format!(
+            "    Allocated carbon: {} to {} owners",
+            tonnes(allocation.values().sum()),
+            allocation.len()
+        ));
+
+        // Same whole-dollar comparison `tonnes` publishes verification failures with
+        let verdict = match (value_handled / 1_000_000).cmp(&(total_value / 1_000_000)) {
+            std::cmp::Ordering::Equal => "OK".to_string(),
+            std::cmp::Ordering::Less => format!("MISSING {}", usd(total_value - value_handled)),
+            std::cmp::Ordering::Greater => format!("OVER {}", usd(value_handled - total_value)),
+        };
+        lines.push(format!("  {}", verdict));
+        lines.push("".to_string());
+    }
+    Ok(lines)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all("statements")?;
+
+    let statements = load_statements()?;
+    for (owner, statement) in statements.iter() {
+        write_pdf(
+            &("statements/".to_owned() + owner + ".pdf"),
+            &statement_lines(owner, statement),
+        )?;
+    }
+    println!("Wrote {} owner statements", statements.len());
+
+    write_pdf("statements/reconciliation.pdf", &reconciliation_lines()?)?;
+    println!("Wrote reconciliation report");
+
+    Ok(())
+}
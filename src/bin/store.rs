@@ -0,0 +1,160 @@
+use clap::{Parser, Subcommand};
+use rusqlite::Connection;
+use rust_playground::{
+    model::normalize,
+    store::{self, now, RunInfo, DEFAULT_DB},
+};
+
+/// Keeps every snapshot run in a SQLite database.
+#[derive(Debug, Parser)]
+struct Args {
+    #[arg(long, default_value = DEFAULT_DB)]
+    db: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Record the current `output/` and `snapshot/` files, using `output/meta.json`.
+    /// `scan` and `total` record them too with `--record` or `SNAPSHOT_DB`.
+    Record,
+    /// Import `output/` and `snapshot/` files produced before `output/meta.json` existed
+    Import {
+        #[arg(long)]
+        block: u64,
+        #[arg(long)]
+        timestamp: Option<u64>,
+    },
+    /// List recorded runs
+    Runs,
+    /// Holdings of an owner at each recorded run
+    Holdings { owner: String },
+    /// Tokens that changed owner between two runs
+    Changes { from: i64, to: i64 },
+}
+
+fn runs(conn: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, block, timestamp, config_hash, (SELECT COUNT(*) FROM tokens WHERE run_id = runs.id)
+         FROM runs ORDER BY id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, u64>(1)?,
+            row.get::<_, u64>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, i64>(4)?,
+        ))
+    })?;
+    for row in rows {
+        let (id, block, timestamp, config_hash, tokens) = row?;
+        println!(
+            "Run {}: block {}, timestamp {}, config {}, {} tokens",
+            id,
+            block,
+            timestamp,
+            config_hash.unwrap_or("-".to_string()),
+            tokens
+        );
+    }
+    Ok(())
+}
+
+fn holdings(conn: &Connection, owner: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stmt = conn.prepare(
+        "SELECT runs.id, runs.block, owners.snapshot, owners.value, owners.value_offset, owners.value_yielder
+         FROM owners JOIN runs ON runs.id = owners.run_id
+         WHERE owners.owner = ?1
+         ORDER BY runs.id, owners.snapshot",
+    )?;
+    let rows = stmt.query_map([normalize(owner)?], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, u64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, String>(5)?,
+        ))
+    })?;
+    for row in rows {
+        let (id, block, snapshot, value, value_offset, value_yielder) = row?;
+        let dollars = |value: &str| -> Result<u128, String> {
+            Ok(value
+                .parse::<u128>()
+                .map_err(|_| format!("run {}: invalid value {}", id, value))?
+                / 1_000_000)
+        };
+        println!(
+            "Run {} (block {}) {}: value ${}, offsetter ${}, yielder ${}",
+            id,
+            block,
+            snapshot,
+            dollars(&value)?,
+            dollars(&value_offset)?,
+            dollars(&value_yielder)?
+        );
+    }
+    Ok(())
+}
+
+fn changes(conn: &Connection, from: i64, to: i64) -> Result<(), Box<dyn std::error::Error>> {
+    let changes = store::token_changes(conn, from, to)?;
+    let (mut added, mut removed, mut moved) = (0, 0, 0);
+    for change in &changes {
+        let (old_owner, new_owner) = match (&change.old_owner, &change.new_owner) {
+            (None, Some(new_owner)) => {
+                added += 1;
+                ("added", new_owner.as_str())
+            }
+            (Some(old_owner), None) => {
+                removed += 1;
+                (old_owner.as_str(), "removed")
+            }
+            (Some(old_owner), Some(new_owner)) => {
+                moved += 1;
+                (old_owner.as_str(), new_owner.as_str())
+            }
+            (None, None) => unreachable!(),
+        };
+        println!(
+            "{} {} #{}: {} -> {}",
+            change.project, change.version, change.token_id, old_owner, new_owner
+        );
+    }
+    println!(
+        "{} tokens changed owner, {} were added and {} removed between runs {} and {}",
+        moved, added, removed, from, to
+    );
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let mut conn = store::open(&args.db)?;
+
+    match args.command {
+        Command::Record => {
+            let run = RunInfo::from_meta()?;
+            store::record_tokens(&mut conn, &run)?;
+            store::record_owners(&mut conn, &run)?;
+        }
+        Command::Import { block, timestamp } => {
+            let run = RunInfo {
+                block,
+                block_hash: None,
+                timestamp: timestamp.unwrap_or(now()),
+                config_hash: None,
+            };
+            store::record_tokens(&mut conn, &run)?;
+            store::record_owners(&mut conn, &run)?;
+        }
+        Command::Runs => runs(&conn)?,
+        Command::Holdings { owner } => holdings(&conn, &owner)?,
+        Command::Changes { from, to } => changes(&conn, from, to)?,
+    }
+
+    Ok(())
+}
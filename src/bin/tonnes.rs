@@ -1,105 +1,441 @@
+use cainome::cairo_serde::{CairoSerde, ContractAddress, U256};
+use rust_playground::{
+    bindings::yielder::YielderReader,
+    contracts::by_name,
+    decode::{u256_to_felt, u256_to_u128},
+    entrypoints::Selectors,
+    erc3525,
+    events::Publisher,
+    exclusions::{load_exclusions, owner_rule, Exclusion},
+    model::{
+        load_adjustments, load_project_config, read_json, AbsorptionSource, Adjustment,
+        ProjectConfig, PROJECTS,
+    },
+    rpc::{yielder_depositors, Batcher, CachedTransport},
+};
 use serde_json::{from_reader, json, Value};
-use starknet::{core::types::Felt, macros::felt};
-use std::{collections::HashMap, fs::File, vec};
-
-fn main() {
-    let banegas = vec!["BanegasFarmv1.json".into(), "BanegasFarmv2.json".into()];
-    let delicias = vec![
-        "LasDeliciasv1.json".into(),
-        "LasDeliciasv2.json".into(),
-        "LasDelicias_old.json".into(),
-    ];
-    let manjarisoa: Vec<String> = vec![
-        "ManjarisoBronzev1.json".into(),
-        "ManjarisoSilverv1.json".into(),
-        //"ManjarisoGoldv1.json".into(),
-        "Manjarisoav2.json".into(),
-    ];
-    let karathuru = vec![
-        "Karathuruv2.json".into(),
-        "Karathuru_undeployed.json".into(),
-    ];
-
-    // remove farming contracts from snapshots
-    let banegas_farming = vec![
-        felt!("0x03d25473be5a6316f351e8f964d0c303357c006f7107779f648d9879b7c6d58a"),
-        felt!("0x0324b531f731100b494e2f978a26b20b5870585dd96d9f1166b43a28ebbb8aba"),
-    ];
-    let delicias_farming = vec![
-        felt!("0x00426d4e86913759bcc49b7f992b1fe62e6571e8f8089c23d95fea815dbad471"),
-        felt!("0x022f40128af9798a0b734874fd993bbab6cf75845f26f844cb151b7041132c6d"),
-        felt!("0x030172515f274abe1a5938108f083fc7da90481933e34458f0352eebea32560d"),
-        felt!("0x04774636985a87ae06f9eab5d7d25fb75ef8be0e04fe3444b0277bad17465e51"),
-    ];
-    let manjarisoa_farming = vec![
-        felt!("0x03afe61732ed9b226309775ac4705129319729d3bee81da5632146ffd72652ae"),
-        felt!("0x04258037980fcc15083cde324abe1861ac00d4d48b7d60d76b5efd6f57e59e73"),
-    ];
-    let karathuru_farming = vec![];
-
-    compute_amount(
-        "Banegas".into(),
-        banegas,
-        banegas_farming,
-        1573_000_000_000,
-        17600_000_000,
-    );
-    compute_amount(
-        "Delicias".into(),
-        delicias,
-        delicias_farming,
-        3603_000_000_000,
-        39600_000_000,
-    );
-    compute_amount(
-        "Manjarisoa".into(),
-        manjarisoa,
-        manjarisoa_farming,
-        8000_000_000_000,
-        121099_000_000,
+use starknet::{
+    core::types::{BlockId, Felt, FunctionCall},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Url,
+    },
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    vec,
+};
+
+/// Tonnes are kept with 9 decimals.
+const TONNES: u128 = 1_000_000_000;
+
+/// Chain access shared by every project.
+struct Chain<'a> {
+    provider: &'a JsonRpcClient<CachedTransport>,
+    batcher: &'a Batcher,
+    selectors: &'a mut Selectors,
+    block_id: BlockId,
+}
+
+impl Chain<'_> {
+    /// `name(slot)` of `project`, a single integer.
+    async fn slot_call(
+        &mut self,
+        project: Felt,
+        name: &str,
+        slot: &U256,
+    ) -> Result<u128, Box<dyn std::error::Error>> {
+        let calldata = vec![slot.low.into(), slot.high.into()];
+        let result = self
+            .selectors
+            .call(self.provider, project, name, calldata, self.block_id)
+            .await?;
+        let value = result.first().ok_or(format!("{} returned nothing", name))?;
+        Ok(u128::try_from(*value)
+            .map_err(|_| format!("{} returned {}, more than a u128", name, value))?)
+    }
+
+    /// Absorption units per tonne of `slot`, read from the project
+    /// `get_ton_equivalent` rather than assumed.
+    async fn ton_equivalent(
+        &mut self,
+        project: Felt,
+        slot: &U256,
+    ) -> Result<u128, Box<dyn std::error::Error>> {
+        let units = self.slot_call(project, "get_ton_equivalent", slot).await?;
+        // Each unit must be a whole number of 1e-9 tonnes
+        if units == 0 || !TONNES.is_multiple_of(units) {
+            return Err(format!(
+                "unsupported ton equivalent {} for slot {}",
+                units,
+                u256_to_felt(slot)
+            )
+            .into());
+        }
+        Ok(units)
+    }
+}
+
+/// Tonnes of a project with a yielder, from its `get_max_absorption`. The
+/// depositors' `get_absorption_of` must add up to `get_total_absorption`.
+async fn yielder_tonnes(
+    chain: &mut Chain<'_>,
+    publisher: &Publisher,
+    project: &str,
+    yielder: Felt,
+) -> Result<u128, Box<dyn std::error::Error>> {
+    let block_id = chain.block_id;
+    let reader = YielderReader::new(yielder, chain.provider);
+    let project_address = reader
+        .get_carbonable_project_address()
+        .block_id(block_id)
+        .call()
+        .await?;
+    let slot = reader
+        .get_carbonable_project_slot()
+        .block_id(block_id)
+        .call()
+        .await?;
+    let units = chain.ton_equivalent(project_address.0, &slot).await?;
+    let max_absorption = u256_to_u128(
+        &reader
+            .get_max_absorption()
+            .block_id(block_id)
+            .call()
+            .await?,
+    )?;
+    let total_absorption = u256_to_u128(
+        &reader
+            .get_total_absorption()
+            .block_id(block_id)
+            .call()
+            .await?,
+    )?;
+    let tonnes = max_absorption * (TONNES / units);
+    println!(
+        "On-chain absorption for {} is {} tonnes ({} units per tonne), {} absorbed so far",
+        project,
+        tonnes / TONNES,
+        units,
+        total_absorption / units
     );
-    compute_amount(
-        "Karathuru".into(),
-        karathuru,
-        karathuru_farming,
-        70589_000_000_000,
-        367909_870_000,
+
+    let depositors = yielder_depositors(chain.provider, yielder, block_id).await?;
+    let calls: Vec<FunctionCall> = depositors
+        .iter()
+        .map(|depositor| {
+            reader
+                .get_absorption_of(&ContractAddress(*depositor))
+                .call_raw
+        })
+        .collect();
+    let mut absorbed_by_depositors = 0;
+    for data in chain
+        .batcher
+        .aggregate(chain.provider, &calls, block_id)
+        .await?
+    {
+        absorbed_by_depositors += u256_to_u128(&U256::cairo_deserialize(&data, 0)?)?;
+    }
+    if absorbed_by_depositors != total_absorption {
+        println!(
+            "Absorption of the {} depositors of {} adds up to {}, the yielder total is {}",
+            depositors.len(),
+            project,
+            absorbed_by_depositors,
+            total_absorption
+        );
+        publisher
+            .publish(
+                "verification.failed",
+                json!({
+                    "project": project,
+                    "yielder": yielder.to_hex_string(),
+                    "reason": "depositor absorption differs from the yielder total",
+                    "depositors_absorption": u64::try_from(absorbed_by_depositors)?,
+                    "total_absorption": u64::try_from(total_absorption)?,
+                }),
+            )
+            .await;
+    }
+    Ok(tonnes)
+}
+
+/// Tonnes of a project without a yielder, from the `get_final_absorption` of the
+/// only slot of its ERC-3525 contract.
+async fn project_contract_tonnes(
+    chain: &mut Chain<'_>,
+    project: &str,
+    contract: Felt,
+) -> Result<u128, Box<dyn std::error::Error>> {
+    let slots = erc3525::slots(chain.provider, chain.batcher, contract, chain.block_id).await?;
+    let [slot] = slots.as_slice() else {
+        return Err(format!(
+            "{}: {} has {} slots, expected one",
+            project,
+            contract.to_hex_string(),
+            slots.len()
+        )
+        .into());
+    };
+    let units = chain.ton_equivalent(contract, slot).await?;
+    let final_absorption = chain
+        .slot_call(contract, "get_final_absorption", slot)
+        .await?;
+    let current_absorption = chain
+        .slot_call(contract, "get_current_absorption", slot)
+        .await?;
+    let tonnes = final_absorption * (TONNES / units);
+    println!(
+        "On-chain absorption for {} is {} tonnes ({} units per tonne), {} absorbed so far",
+        project,
+        tonnes / TONNES,
+        units,
+        current_absorption / units
     );
+    Ok(tonnes)
+}
+
+/// Reads a project's tonnes from chain at the snapshot block, converted with the
+/// project's ton equivalent. A divergence from the configured total fails, unless
+/// the project is listed in `ACCEPT_ONCHAIN_TONNES`, in which case the on-chain
+/// total is used.
+async fn project_tonnes(
+    chain: &mut Chain<'_>,
+    publisher: &Publisher,
+    config: &ProjectConfig,
+) -> Result<u128, Box<dyn std::error::Error>> {
+    let project = config.name.as_str();
+    let contract =
+        |name: &str| by_name(name).ok_or(format!("{}: unknown contract {}", project, name));
+    let (source, tonnes) = match &config.absorption {
+        AbsorptionSource::Yielder(name) => {
+            let yielder = contract(name)?;
+            (
+                yielder,
+                yielder_tonnes(chain, publisher, project, yielder).await?,
+            )
+        }
+        AbsorptionSource::Project(name) => {
+            let address = contract(name)?;
+            (
+                address,
+                project_contract_tonnes(chain, project, address).await?,
+            )
+        }
+    };
+
+    let configured = config.tonnes;
+    if tonnes != configured {
+        println!(
+            "Divergence for {}: configured {} tonnes, on-chain {} tonnes",
+            project, configured, tonnes
+        );
+        publisher
+            .publish(
+                "verification.failed",
+                json!({
+                    "project": project,
+                    "contract": source.to_hex_string(),
+                    "reason": "configured tonnes differ from on-chain absorption",
+                    "configured_tonnes": u64::try_from(configured)?,
+                    "onchain_tonnes": u64::try_from(tonnes)?,
+                }),
+            )
+            .await;
+        let accepted = std::env::var("ACCEPT_ONCHAIN_TONNES").unwrap_or_default();
+        if !accepted.split(',').any(|name| name.trim() == project) {
+            return Err(format!(
+                "{}: configured {} tonnes differ from on-chain {}, update projects.json or set ACCEPT_ONCHAIN_TONNES={}",
+                project, configured, tonnes, project
+            )
+            .into());
+        }
+        println!(
+            "Using on-chain tonnes for {} (ACCEPT_ONCHAIN_TONNES)",
+            project
+        );
+    }
+    Ok(tonnes)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let publisher = Publisher::from_env("ALLOCATION_SUBJECT_PREFIX", "allocation").await?;
+
+    let url = Url::parse("https://rpc.starknet.lava.build:443")?;
+    let provider = JsonRpcClient::new(CachedTransport::new(HttpTransport::new(url.clone())).await?);
+    let batcher = Batcher::from_env(CachedTransport::new(HttpTransport::new(url)).await?)?;
+    let mut selectors = Selectors::default();
+    // Absorption is read at the block the snapshot was taken at
+    let metadata = read_json("output/meta.json")?.ok_or("output/meta.json is missing")?;
+    let mut chain = Chain {
+        provider: &provider,
+        batcher: &batcher,
+        selectors: &mut selectors,
+        block_id: BlockId::Number(
+            metadata["block_number"]
+                .as_u64()
+                .ok_or("output/meta.json has no block_number")?,
+        ),
+    };
+
+    let configs = load_project_config()?;
+    // farming contracts and other owners to remove from snapshots
+    let exclusions = load_exclusions()?;
+    // off-chain credits and debits, applied after the snapshots
+    let adjustments = load_adjustments()?;
+    let mut summaries = vec![];
+
+    for (project, snapshots) in PROJECTS {
+        let config = configs
+            .iter()
+            .find(|config| config.name == project)
+            .ok_or(format!("projects.json has no {}", project))?;
+        let tonnes = project_tonnes(&mut chain, &publisher, config).await?;
+        let summary = compute_amount(
+            project.into(),
+            snapshots
+                .iter()
+                .map(|name| name.to_string() + ".json")
+                .collect(),
+            &exclusions,
+            &adjustments,
+            tonnes,
+            config.value,
+        )?;
+        summaries.push(summary.clone());
+        publish_summary(&publisher, summary).await;
+    }
+
+    let mut excluded = vec![];
+    let mut applied = vec![];
+    for summary in summaries.iter_mut() {
+        applied.extend(summary["adjustments"].as_array().unwrap().clone());
+        if let Some(Value::Array(entries)) = summary.as_object_mut().unwrap().remove("excluded") {
+            excluded.extend(entries);
+        }
+    }
+    std::fs::write(
+        "output/allocation_exclusions.json",
+        serde_json::to_string_pretty(&excluded)?,
+    )?;
+    // Audit trail of the manual adjustments, with the tonnes each one moved
+    std::fs::write(
+        "output/adjustments_applied.json",
+        serde_json::to_string_pretty(&applied)?,
+    )?;
+    // Per project totals, read by the `statements` reconciliation report
+    std::fs::write(
+        "output/allocation.json",
+        serde_json::to_string_pretty(&summaries)?,
+    )?;
+
+    publisher.flush().await;
+
+    Ok(())
+}
+
+/// Publishes a project's completion, and a verification failure when the
+/// allocated value does not add up to the project value.
+async fn publish_summary(publisher: &Publisher, summary: Value) {
+    if summary["value_handled"].as_u64().unwrap() / 1_000_000
+        != summary["total_value"].as_u64().unwrap() / 1_000_000
+    {
+        publisher
+            .publish("verification.failed", summary.clone())
+            .await;
+    }
+    publisher.publish("project.completed", summary).await;
 }
 
 fn compute_amount(
     project: String,
     snapshots: Vec<String>,
-    blacklist: Vec<Felt>,
+    exclusions: &[Exclusion],
+    adjustments: &[Adjustment],
     total_tonnes: u128,
     total_value: u128,
-) {
-    let mut amount_map = HashMap::new();
+) -> Result<Value, Box<dyn std::error::Error>> {
+    // Keyed by address so padded and unpadded spellings of an owner add up
+    let mut amount_map: HashMap<Felt, u128> = HashMap::new();
+    let mut excluded = vec![];
+    let mut excluded_value = 0;
     for snapshot in snapshots {
-        let file =
-            File::open("snapshot/".to_owned() + &snapshot).expect("file should open read only");
-        let data: Value = from_reader(file).expect("file should be proper JSON");
+        let file = File::open("snapshot/".to_owned() + &snapshot)?;
+        let data: Value = from_reader(file)?;
 
         for (owner, token_info) in data.as_object().unwrap() {
-            if blacklist.contains(&Felt::from_hex(owner).unwrap()) {
-                continue;
-            };
             let value: u128 = token_info["value"].as_u64().unwrap().into();
             let value_offset: u128 = token_info["value_offset"].as_u64().unwrap().into();
             let value_yielder: u128 = token_info["value_yielder"].as_u64().unwrap().into();
 
+            let address = Felt::from_hex(owner)?;
+            if let Some(rule) = owner_rule(exclusions, &project, &address) {
+                let reason = rule.reason();
+                let removed = value + value_offset + value_yielder;
+                println!(
+                    "Excluded {} from {} (${}): {}",
+                    owner,
+                    snapshot,
+                    removed / 1_000_000,
+                    reason
+                );
+                excluded_value += removed;
+                excluded.push(json!({
+                    "project": project,
+                    "snapshot": snapshot,
+                    "owner": owner,
+                    "value": u64::try_from(removed)?,
+                    "reason": reason,
+                    "unconfirmed": rule.unconfirmed(),
+                }));
+                continue;
+            };
+
             let new_value = value + value_offset + value_yielder;
             if new_value == 0 {
                 continue;
             }
-            let current = *amount_map.entry(owner.to_owned()).or_insert(0_u128);
+            let current = *amount_map.entry(address).or_insert(0_u128);
             let additional = if current == 0 { new_value } else { value };
 
             let amount: u128 = current + (additional * total_tonnes) / total_value;
-            amount_map.insert(owner.to_owned(), amount);
+            amount_map.insert(address, amount);
         }
     }
 
+    let mut applied = vec![];
+    for adjustment in adjustments.iter().filter(|a| a.project == project) {
+        let current = *amount_map.entry(adjustment.owner).or_insert(0_u128);
+        let tonnes = (u128::from(adjustment.amount.unsigned_abs()) * total_tonnes) / total_value;
+        let amount = if adjustment.amount >= 0 {
+            current + tonnes
+        } else {
+            current.checked_sub(tonnes).ok_or_else(|| {
+                format!(
+                    "debit of {} tonnes exceeds the {} allocated: {:?}",
+                    tonnes, current, adjustment
+                )
+            })?
+        };
+        amount_map.insert(adjustment.owner, amount);
+        println!(
+            "Adjusted {} by ${} ({}, {}): {}",
+            adjustment.owner.to_hex_string(),
+            adjustment.amount / 1_000_000,
+            adjustment.author,
+            adjustment.date,
+            adjustment.justification
+        );
+        applied.push(json!({
+            "adjustment": adjustment,
+            "tonnes_before": u64::try_from(current)?,
+            "tonnes_after": u64::try_from(amount)?,
+        }));
+    }
+
     let mut total_value_handled = 0;
     let mut total_tonnes_handled = 0;
     for (_, amount) in amount_map.iter() {
@@ -107,13 +443,15 @@ fn compute_amount(
         total_tonnes_handled += amount
     }
 
-    let json_output = json!(amount_map);
+    let json_output: BTreeMap<String, u128> = amount_map
+        .iter()
+        .map(|(owner, amount)| (owner.to_hex_string(), *amount))
+        .collect();
 
     std::fs::write(
         "tonnes/".to_owned() + project.as_str() + ".json",
-        serde_json::to_string_pretty(&json_output).unwrap(),
-    )
-    .unwrap();
+        serde_json::to_string_pretty(&json_output)?,
+    )?;
 
     println!(
         "Total tonnes handled for {} is {} out of {} tonnes",
@@ -127,4 +465,22 @@ fn compute_amount(
         total_value_handled / 1_000_000,
         total_value / 1_000_000
     );
+
+    println!(
+        "Total value excluded for {} is ${}",
+        project,
+        excluded_value / 1_000_000
+    );
+
+    Ok(json!({
+        "project": project,
+        "owners": amount_map.len(),
+        "excluded_value": u64::try_from(excluded_value)?,
+        "excluded": excluded,
+        "adjustments": applied,
+        "tonnes_handled": u64::try_from(total_tonnes_handled)?,
+        "total_tonnes": u64::try_from(total_tonnes)?,
+        "value_handled": u64::try_from(total_value_handled)?,
+        "total_value": u64::try_from(total_value)?,
+    }))
 }
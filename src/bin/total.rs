@@ -1,3 +1,4 @@
+use rust_playground::store;
 use serde_json::{from_reader, json, Value};
 use std::{collections::HashMap, fs::File};
 
@@ -8,9 +9,14 @@ struct ValueInfo {
     value_yielder: u64,
 }
 
-fn main() {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     total("v2");
     total("v1");
+
+    // Recorded under the run `scan` wrote to `output/meta.json`
+    println!();
+    store::record_if_requested(store::record_owners)?;
+    Ok(())
 }
 
 fn total(version: &str) {
@@ -1,20 +1,61 @@
-use serde_json::{json, Value};
+use clap::Parser;
+use rust_playground::{
+    contracts::V1_COLLECTIONS,
+    decode::decode_string,
+    entrypoints::Selectors,
+    erc721::{self, Enumeration},
+    rpc::{Batcher, CachedTransport},
+};
+use serde_json::json;
 use starknet::{
-    core::types::{BlockId, BlockTag, Felt, FunctionCall},
-    macros::{felt, selector},
+    core::types::{BlockId, Felt, FunctionCall},
+    macros::selector,
     providers::{
         jsonrpc::{HttpTransport, JsonRpcClient},
         Provider, Url,
     },
 };
-use std::{collections::HashMap, time::Instant};
+use std::time::Instant;
+
+/// Snapshots ERC-721 contracts, replaying `Transfer` events when they are not enumerable.
+#[derive(Debug, Parser)]
+struct Args {
+    /// ERC-721 contract to scan, repeat for several. Every v1 collection of
+    /// `rust_playground::contracts` when omitted
+    #[arg(long = "contract", value_parser = Felt::from_hex)]
+    contracts: Vec<Felt>,
+    /// Block number to read at, latest when omitted
+    #[arg(long)]
+    block: Option<u64>,
+    #[arg(
+        long,
+        default_value = "https://starknet-mainnet.public.blastapi.io/rpc/v0_7"
+    )]
+    rpc: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct TokenRecord {
+    token_id: String,
+    owner: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CollectionSnapshot {
+    address: String,
+    name: String,
+    enumeration: Enumeration,
+    tokens: Vec<TokenRecord>,
+}
 
 async fn scan_project(
-    provider: &JsonRpcClient<HttpTransport>,
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    selectors: &mut Selectors,
     contract_address: Felt,
-) -> Result<Value, Box<dyn std::error::Error>> {
+    block_id: BlockId,
+) -> Result<CollectionSnapshot, Box<dyn std::error::Error>> {
     println!("\nScanning project: {}", contract_address.to_hex_string());
-    let mut token_owners = HashMap::new();
 
     let name_call_result = provider
         .call(
@@ -23,120 +64,95 @@ async fn scan_project(
                 entry_point_selector: selector!("name"),
                 calldata: vec![],
             },
-            BlockId::Tag(BlockTag::Latest),
+            block_id,
         )
         .await?;
-    let name = felt_to_string(name_call_result[0].clone());
-
-    // Get total supply
-    let total_supply_call_result = provider
-        .call(
-            FunctionCall {
-                contract_address,
-                entry_point_selector: selector!("totalSupply"),
-                calldata: vec![],
-            },
-            BlockId::Tag(BlockTag::Latest),
-        )
-        .await?;
-    let total_supply: usize = total_supply_call_result[0]
-        .to_bigint()
-        .to_string()
-        .parse()
-        .unwrap_or(0_usize);
-
-    println!("Total supply for {}: {}", name, total_supply);
-
-    if total_supply == 0 {
-        return Ok(json!({
-            "address": contract_address.to_hex_string(),
-            "token_owners": token_owners,
-        }));
-    }
-
-    let mut proms = Vec::with_capacity(total_supply - 1);
-    for i in 0..total_supply - 1 {
-        proms.push(provider.call(
-            FunctionCall {
-                contract_address,
-                entry_point_selector: selector!("tokenByIndex"),
-                calldata: vec![Felt::from(i), Felt::ZERO],
-            },
-            BlockId::Tag(BlockTag::Latest),
-        ));
-    }
-
-    let all_tokens: Vec<Vec<Felt>> = futures::future::join_all(proms)
-        .await
-        .into_iter()
-        .map(|x| x.expect("Failed to fetch token index"))
+    let name = decode_string(&name_call_result).unwrap_or_default();
+
+    let (enumeration, token_ids) =
+        erc721::token_ids(provider, batcher, selectors, contract_address, block_id).await?;
+
+    // Both paths read the owner at the pinned block
+    let owner_of = match token_ids.first() {
+        Some(id) => {
+            let probe = vec![*id, Felt::ZERO];
+            selectors
+                .resolve(provider, contract_address, "ownerOf", probe, block_id)
+                .await?
+        }
+        None => selector!("ownerOf"),
+    };
+    let calls: Vec<FunctionCall> = token_ids
+        .iter()
+        .map(|id| FunctionCall {
+            contract_address,
+            entry_point_selector: owner_of,
+            calldata: vec![*id, Felt::ZERO],
+        })
+        .collect();
+    let results = batcher.aggregate(provider, &calls, block_id).await?;
+
+    let tokens: Vec<TokenRecord> = token_ids
+        .iter()
+        .zip(results)
+        .map(|(id, data)| TokenRecord {
+            token_id: id.to_bigint().to_string(),
+            owner: data[0].to_hex_string(),
+        })
         .collect();
 
-    for token in all_tokens.iter() {
-        let token_id = token[0].to_bigint().to_string();
-        match provider
-            .call(
-                FunctionCall {
-                    contract_address,
-                    entry_point_selector: selector!("ownerOf"),
-                    calldata: token.clone(),
-                },
-                BlockId::Tag(BlockTag::Latest),
-            )
-            .await
-        {
-            Ok(res) => {
-                let owner = res[0].to_hex_string();
-                token_owners.insert(token_id, owner);
-            }
-            Err(e) => {
-                eprintln!(
-                    "Error fetching owner for token ID {} in project {}: {}",
-                    token[0].to_bigint(),
-                    contract_address.to_hex_string(),
-                    e
-                );
-            }
-        }
+    if tokens.len() != token_ids.len() {
+        return Err(format!(
+            "{}: fetched {} token owners for {} tokens",
+            name,
+            tokens.len(),
+            token_ids.len()
+        )
+        .into());
     }
-
-    // Return structured JSON object
-    Ok(json!({
-        "address": contract_address.to_hex_string(),
-        "token_owners": token_owners,
-    }))
+    println!("{}: {} tokens", name, tokens.len());
+
+    Ok(CollectionSnapshot {
+        address: contract_address.to_hex_string(),
+        name,
+        enumeration,
+        tokens,
+    })
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting Starknet multi-project token scanner...");
     let start_time = Instant::now();
+    let args = Args::parse();
 
     // Initialize provider
-    let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(
-        "https://starknet-mainnet.public.blastapi.io/rpc/v0_7",
-    )?));
+    let url = Url::parse(&args.rpc)?;
+    let provider = JsonRpcClient::new(CachedTransport::new(HttpTransport::new(url.clone())).await?);
+    let batcher = Batcher::from_env(CachedTransport::new(HttpTransport::new(url)).await?)?;
+    let mut selectors = Selectors::default();
     println!("Provider initialized successfully");
 
-    // Array of project addresses
-    let project_addresses = vec![
-        BANEGAS,
-        LAS_DELICIAS,
-        MANJARISO_BRONZE,
-        MANJARISO_SILVER,
-        MANJARISO_GOLD,
-    ];
+    // Pin every call to the same block so the snapshot is consistent
+    let block_number = match args.block {
+        Some(block) => block,
+        None => provider.block_number().await?,
+    };
+    let block_id = BlockId::Number(block_number);
+    println!("Snapshot at block {}", block_number);
 
     let mut results = Vec::new();
 
     // Scan each project
-    for address in project_addresses {
-        if let Ok(project_result) = scan_project(&provider, address).await {
-            results.push(project_result);
-        }
+    let contracts = match args.contracts.is_empty() {
+        true => V1_COLLECTIONS.to_vec(),
+        false => args.contracts,
+    };
+    for address in contracts {
+        results.push(scan_project(&provider, &batcher, &mut selectors, address, block_id).await?);
     }
 
-    // Convert results to JSON and print
+    // Convert results to JSON
     let json_output = json!(results);
 
     let duration = start_time.elapsed();
@@ -150,19 +166,3 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
-
-const BANEGAS: Felt = felt!("0x04047810e4f759336f941a16b6de9d8d2f934e976b9a9431a2964646df9025c6");
-const LAS_DELICIAS: Felt =
-    felt!("0x00ebf4bbab9c934fa0212c9358331d4a9543ad66a7e0007d4a720cfa6b56061e");
-const MANJARISO_BRONZE: Felt =
-    felt!("0x0541b5dd5fae206ceccaf4eeb0642e4c04d456c5bc296eab047c9414bdad4f09");
-const MANJARISO_SILVER: Felt =
-    felt!("0x06191013bbd6bcf11d69f3d60d20f7aa03439d7011bd511d13122de2275dce21");
-const MANJARISO_GOLD: Felt =
-    felt!("0x061bcc33b0469cd072ad47813a1efd250fd36a28425d774c31c8f33c87306e8e");
-
-fn felt_to_string(number: Felt) -> String {
-    String::from_utf8(number.to_bytes_be().to_vec())
-        .expect("Invalid UTF-8")
-        .replace("\0", "")
-}
@@ -1,172 +1,77 @@
+use clap::Parser;
+use rust_playground::{
+    contracts::V2_PROJECTS,
+    decode::u256_to_felt,
+    erc3525,
+    rpc::{Batcher, CachedTransport},
+};
 use serde_json::json;
 use starknet::{
-    core::types::{BlockId, BlockTag, Felt, FunctionCall},
-    macros::{felt, selector},
+    core::types::{BlockId, Felt},
     providers::{
         jsonrpc::{HttpTransport, JsonRpcClient},
         Provider, Url,
     },
 };
-use std::{
-    collections::HashMap,
-    time::{Duration, Instant},
-};
-use tokio::time::sleep;
+use std::{collections::BTreeMap, time::Instant};
+
+/// Snapshots ERC-3525 contracts by address alone, discovering their slots.
+#[derive(Debug, Parser)]
+struct Args {
+    /// ERC-3525 contract to scan, repeat for several. Every v2 project of
+    /// `rust_playground::contracts` when omitted
+    #[arg(long = "contract", value_parser = Felt::from_hex)]
+    contracts: Vec<Felt>,
+    /// Block number to read at, latest when omitted
+    #[arg(long)]
+    block: Option<u64>,
+    #[arg(long, default_value = "https://rpc.starknet.lava.build:443")]
+    rpc: String,
+}
 
 #[derive(Debug, serde::Serialize)]
 struct TokenInfo {
     owner: String,
     value: String,
+    /// Address approved for the token, `0x0` when none
+    approved: String,
 }
 
-pub const RATE_LIMIT: Duration = Duration::from_millis(100);
-
+/// Discovers every slot of `contract_address`, then enumerates each slot's tokens
+/// and reads their owner, value and approval.
 async fn scan_slot_project(
-    provider: &JsonRpcClient<HttpTransport>,
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
     contract_address: Felt,
-) -> Result<HashMap<String, HashMap<String, TokenInfo>>, Box<dyn std::error::Error>> {
+    block_id: BlockId,
+) -> Result<BTreeMap<String, BTreeMap<String, TokenInfo>>, Box<dyn std::error::Error>> {
     println!(
         "\nScanning slot-based project: {}",
         contract_address.to_hex_string()
     );
-    let mut slot_tokens = HashMap::new();
-
-    // Get total slot count
-    let slot_count_result = provider
-        .call(
-            FunctionCall {
-                contract_address,
-                entry_point_selector: selector!("slot_count"),
-                calldata: vec![],
-            },
-            BlockId::Tag(BlockTag::Latest),
-        )
-        .await?;
-
-    sleep(RATE_LIMIT).await; // Rate limit delay
-
-    let slot_count: usize = slot_count_result[0]
-        .to_bigint()
-        .to_string()
-        .parse()
-        .unwrap_or(0_usize);
-    println!(
-        "Total slots for {}: {}",
-        contract_address.to_hex_string(),
-        slot_count
-    );
-
-    // Iterate through each slot
-    for slot in 1..slot_count + 1 {
-        println!("Processing slot {}/{}", slot, slot_count);
-        let mut slot_map = HashMap::new();
-
-        // Get token supply in current slot
-        let supply_result = provider
-            .call(
-                FunctionCall {
-                    contract_address,
-                    entry_point_selector: selector!("token_supply_in_slot"),
-                    calldata: vec![Felt::from(slot), Felt::ZERO],
-                },
-                BlockId::Tag(BlockTag::Latest),
-            )
-            .await?;
-
-        sleep(RATE_LIMIT).await; // Rate limit delay
-
-        let slot_supply: usize = supply_result[0]
-            .to_bigint()
-            .to_string()
-            .parse()
-            .unwrap_or(0_usize);
-        println!("Slot {} supply: {}", slot, slot_supply);
-
-        // Process tokens sequentially with rate limiting
-        for index in 0..slot_supply {
-            if index % 10 == 0 {
-                println!(
-                    "Processing token {}/{} in slot {}",
-                    index + 1,
-                    slot_supply,
-                    slot
-                );
-            }
-            // let [slot, index] = [U256::from(slot), U256::from(index)];
-            // pri
-            // Get token
-            let calldata = vec![Felt::from(slot), Felt::ZERO, Felt::from(index), Felt::ZERO];
-            let token = provider
-                .call(
-                    FunctionCall {
-                        contract_address,
-                        entry_point_selector: selector!("token_in_slot_by_index"),
-                        calldata,
+    let mut slot_tokens = BTreeMap::new();
+
+    println!("Discovering slots...");
+    for slot in erc3525::slots(provider, batcher, contract_address, block_id).await? {
+        let token_ids =
+            erc3525::slot_token_ids(provider, batcher, contract_address, &slot, block_id).await?;
+        let tokens =
+            erc3525::read_tokens(provider, batcher, contract_address, &token_ids, block_id).await?;
+
+        let slot_map: BTreeMap<String, TokenInfo> = tokens
+            .into_iter()
+            .map(|token| {
+                (
+                    u256_to_felt(&token.id).to_bigint().to_string(),
+                    TokenInfo {
+                        owner: token.owner.to_hex_string(),
+                        value: token.value.to_string(),
+                        approved: token.approved.to_hex_string(),
                     },
-                    BlockId::Tag(BlockTag::Latest),
                 )
-                .await?;
-
-            sleep(RATE_LIMIT).await; // Rate limit delay
-
-            let token_id = token[0].to_bigint().to_string();
-
-            let owner_result = match provider
-                .call(
-                    FunctionCall {
-                        contract_address,
-                        entry_point_selector: selector!("ownerOf"),
-                        calldata: token.clone(),
-                    },
-                    BlockId::Tag(BlockTag::Latest),
-                )
-                .await
-            {
-                Ok(res) => res[0].to_hex_string(),
-                Err(e) => {
-                    eprintln!(
-                        "Error fetching owner for token {} in slot {}: {}",
-                        token_id, slot, e
-                    );
-                    continue;
-                }
-            };
-
-            sleep(RATE_LIMIT).await; // Rate LIMIT
-
-            let value_result = match provider
-                .call(
-                    FunctionCall {
-                        contract_address,
-                        entry_point_selector: selector!("value_of"),
-                        calldata: token.clone(),
-                    },
-                    BlockId::Tag(BlockTag::Latest),
-                )
-                .await
-            {
-                Ok(res) => res[0].to_bigint().to_string(),
-                Err(e) => {
-                    eprintln!(
-                        "Error fetching value for token {} in slot {}: {}",
-                        token_id, slot, e
-                    );
-                    continue;
-                }
-            };
-
-            sleep(RATE_LIMIT).await; //
-
-            slot_map.insert(
-                token_id,
-                TokenInfo {
-                    owner: owner_result,
-                    value: value_result,
-                },
-            );
-        }
-
-        slot_tokens.insert(slot.to_string(), slot_map);
+            })
+            .collect();
+        slot_tokens.insert(u256_to_felt(&slot).to_bigint().to_string(), slot_map);
     }
 
     Ok(slot_tokens)
@@ -176,27 +81,33 @@ async fn scan_slot_project(
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting Starknet slot-based token scanner...");
     let start_time = Instant::now();
+    let args = Args::parse();
 
-    let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(
-        "https://rpc.starknet.lava.build:443",
-    )?));
+    let url = Url::parse(&args.rpc)?;
+    let provider = JsonRpcClient::new(CachedTransport::new(HttpTransport::new(url.clone())).await?);
+    let batcher = Batcher::from_env(CachedTransport::new(HttpTransport::new(url)).await?)?;
     println!("Provider initialized successfully");
 
-    let project_addresses = vec![
-        felt!("0x0516d0acb6341dcc567e85dc90c8f64e0c33d3daba0a310157d6bba0656c8769"),
-        felt!("0x05a667adc04676fba78a29371561a0bf91dab25847d5dc4709a93a4cfb5ff293"),
-    ];
-
-    let mut results = HashMap::new();
-
-    for address in project_addresses {
-        let token_data = scan_slot_project(&provider, address).await?;
+    // Pin every call to the same block so the snapshot is consistent
+    let block_number = match args.block {
+        Some(block) => block,
+        None => provider.block_number().await?,
+    };
+    let block_id = BlockId::Number(block_number);
+    println!("Snapshot at block {}", block_number);
+
+    let mut results = BTreeMap::new();
+
+    let contracts = match args.contracts.is_empty() {
+        true => V2_PROJECTS.to_vec(),
+        false => args.contracts,
+    };
+    for address in contracts {
+        let token_data = scan_slot_project(&provider, &batcher, address, block_id).await?;
         results.insert(address.to_hex_string(), token_data);
     }
 
     let json_output = json!(results);
-    println!("\nResults JSON:");
-    println!("{}", serde_json::to_string_pretty(&json_output)?);
 
     std::fs::write(
         "output/v2.json",
@@ -0,0 +1,181 @@
+use cainome::cairo_serde::{CairoSerde, U256};
+use clap::Parser;
+use rust_playground::{
+    allocation::load_allocation,
+    decode::u256_to_u128,
+    migration::{load_batches, token_recipient},
+    rpc::{events_of, Batcher, CachedTransport},
+};
+use serde_json::json;
+use starknet::{
+    core::types::{BlockId, Felt, FunctionCall},
+    macros::selector,
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider, Url,
+    },
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// Checks v3 balances at a pinned block against `tonnes/<project>.json`.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Project name, as in `tonnes/<project>.json`
+    #[arg(long)]
+    project: String,
+    /// v3 project contract the allocation was distributed on
+    #[arg(long)]
+    contract: String,
+    #[arg(long, default_value = "1")]
+    token_id: u128,
+    /// Block number the balances are read at
+    #[arg(long)]
+    block: u64,
+    #[arg(long, default_value = "https://rpc.starknet.lava.build:443")]
+    rpc: String,
+    /// Batches directory, recipients found there but not in the allocation are reported
+    #[arg(long, default_value = "batches")]
+    batches: String,
+}
+
+/// Every account `token_id` was ever transferred or minted to up to `block_id`,
+/// from the `TransferSingle` and `TransferBatch` events of `contract`.
+async fn token_holders(
+    provider: &impl Provider,
+    contract: Felt,
+    token_id: u128,
+    block_id: BlockId,
+) -> Result<BTreeSet<Felt>, Box<dyn std::error::Error>> {
+    let mut holders = BTreeSet::new();
+    for key in [selector!("TransferSingle"), selector!("TransferBatch")] {
+        for event in events_of(provider, contract, key, block_id).await? {
+            let to = token_recipient(&event.keys, &event.data, token_id)
+                .map_err(|e| format!("{}: {}", event.transaction_hash.to_hex_string(), e))?;
+            holders.extend(to.filter(|to| *to != Felt::ZERO));
+        }
+    }
+    Ok(holders)
+}
+
+/// Recipients of the batches built for the project, if any.
+fn load_batch_recipients(dir: &Path) -> Result<BTreeSet<Felt>, Box<dyn std::error::Error>> {
+    if !dir.exists() {
+        return Ok(BTreeSet::new());
+    }
+    Ok(load_batches(dir)?
+        .iter()
+        .flat_map(|batch| batch.recipients.iter().map(|r| r.owner))
+        .collect())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let contract = Felt::from_hex(&args.contract)?;
+    let block_id = BlockId::Number(args.block);
+
+    let url = Url::parse(&args.rpc)?;
+    let provider = JsonRpcClient::new(CachedTransport::new(HttpTransport::new(url.clone())).await?);
+    let batcher = Batcher::from_env(CachedTransport::new(HttpTransport::new(url)).await?)?;
+    println!("Provider initialized successfully");
+
+    let allocation = load_allocation(&args.project)?;
+    let mut owners: BTreeSet<Felt> = allocation.keys().cloned().collect();
+    let batch_recipients = load_batch_recipients(&Path::new(&args.batches).join(&args.project))?;
+    owners.extend(batch_recipients);
+    println!("Fetching holders of token {}...", args.token_id);
+    let holders = token_holders(&provider, contract, args.token_id, block_id).await?;
+    println!("Token holders: {}", holders.len());
+    owners.extend(holders);
+
+    let calls: Vec<FunctionCall> = owners
+        .iter()
+        .map(|owner| FunctionCall {
+            contract_address: contract,
+            entry_point_selector: selector!("balance_of"),
+            calldata: vec![*owner, Felt::from(args.token_id), Felt::ZERO],
+        })
+        .collect();
+
+    println!(
+        "Fetching {} balances at block {}...",
+        calls.len(),
+        args.block
+    );
+    let balances = batcher.aggregate(&provider, &calls, block_id).await?;
+
+    let mut missing = BTreeMap::new();
+    let mut short = BTreeMap::new();
+    let mut over_paid = BTreeMap::new();
+    let mut unexpected = BTreeMap::new();
+    let mut total_expected = 0;
+    let mut total_received = 0;
+
+    for (owner, balance) in owners.iter().zip(balances) {
+        let received = u256_to_u128(&U256::cairo_deserialize(&balance, 0)?)
+            .map_err(|e| format!("balance of {}: {}", owner.to_hex_string(), e))?;
+        let expected = allocation.get(owner).cloned().unwrap_or(0);
+        total_expected += expected;
+        total_received += received;
+
+        let entry = json!({ "expected": expected, "received": received });
+        let owner = owner.to_hex_string();
+        if expected == 0 {
+            if received > 0 {
+                unexpected.insert(owner, entry);
+            }
+        } else if received == 0 {
+            missing.insert(owner, entry);
+        } else if received < expected {
+            short.insert(owner, entry);
+        } else if received > expected {
+            over_paid.insert(owner, entry);
+        }
+    }
+
+    println!("Missing recipients: {}", missing.len());
+    println!("Short recipients: {}", short.len());
+    println!("Over-paid recipients: {}", over_paid.len());
+    println!("Unexpected recipients: {}", unexpected.len());
+    println!(
+        "Total received for {} is {} out of {} tonnes",
+        args.project,
+        total_received / 1_000_000_000,
+        total_expected / 1_000_000_000
+    );
+
+    let ok =
+        missing.is_empty() && short.is_empty() && over_paid.is_empty() && unexpected.is_empty();
+    let json_output = json!({
+        "project": args.project,
+        "contract": contract.to_hex_string(),
+        "token_id": args.token_id,
+        "block": args.block,
+        "total_expected": total_expected,
+        "total_received": total_received,
+        "checked_owners": owners.len(),
+        "missing": missing,
+        "short": short,
+        "over_paid": over_paid,
+        "unexpected": unexpected,
+    });
+
+    std::fs::write(
+        "output/verify_".to_owned() + &args.project + ".json",
+        serde_json::to_string_pretty(&json_output)?,
+    )?;
+
+    if !ok {
+        return Err(format!(
+            "migration of {} does not match the allocation",
+            args.project
+        )
+        .into());
+    }
+    println!("OK");
+
+    Ok(())
+}
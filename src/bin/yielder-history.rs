@@ -0,0 +1,215 @@
+use cainome::cairo_serde::{CairoSerde, ContractAddress, U256};
+use clap::Parser;
+use rust_playground::{
+    bindings::yielder::YielderReader,
+    contracts::{BANEGAS_YIELDER, LAS_DELICIAS_YIELDER, MANJARISOA_YIELDER},
+    decode::u256_to_u128,
+    rpc::{yielder_depositors, Batcher, CachedTransport},
+};
+use serde_json::json;
+use starknet::{
+    core::types::{BlockId, Felt},
+    providers::{
+        jsonrpc::{HttpTransport, JsonRpcClient},
+        Provider, Url,
+    },
+};
+use std::collections::BTreeMap;
+
+/// Dumps yielder price and sale time series, with each depositor's realized sale share.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Block number to read at, latest when omitted
+    #[arg(long)]
+    block: Option<u64>,
+    #[arg(long, default_value = "https://rpc.starknet.lava.build:443")]
+    rpc: String,
+}
+
+/// Yielders scanned by `scan`, depositors are found from their `Deposit` events.
+const YIELDERS: [(&str, Felt); 3] = [
+    ("BanegasFarm", BANEGAS_YIELDER),
+    ("LasDelicias", LAS_DELICIAS_YIELDER),
+    ("Manjarisoa", MANJARISOA_YIELDER),
+];
+
+/// Decodes a `Span<u256>`, failing on a value that does not fit in the low word.
+fn u256_span(data: &[Felt]) -> Result<Vec<u128>, Box<dyn std::error::Error>> {
+    let mut values = vec![];
+    for value in Vec::<U256>::cairo_deserialize(data, 0)? {
+        values.push(u256_to_u128(&value)?);
+    }
+    Ok(values)
+}
+
+/// Pairs each time with its value, as `{ "time", "value" }` points, failing
+/// when the two series do not have the same length.
+fn series(
+    name: &str,
+    times: &[u64],
+    values: &[u128],
+) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+    if times.len() != values.len() {
+        return Err(format!(
+            "{} has {} times for {} values",
+            name,
+            times.len(),
+            values.len()
+        )
+        .into());
+    }
+    Ok(times
+        .iter()
+        .zip(values)
+        .map(|(time, value)| json!({ "time": time, "value": value.to_string() }))
+        .collect())
+}
+
+/// Parts per million of `total` that `part` is, rounded down.
+fn share_ppm(part: u128, total: u128) -> u128 {
+    match total {
+        0 => 0,
+        total => part * 1_000_000 / total,
+    }
+}
+
+async fn yielder_history(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    name: &str,
+    yielder: Felt,
+    block_id: BlockId,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    println!("\nReading history of {}", name);
+    let reader = YielderReader::new(yielder, provider);
+    let calls = vec![
+        reader.get_price_times().call_raw,
+        reader.get_prices().call_raw,
+        reader.get_updated_prices().call_raw,
+        reader.get_cumsale_times().call_raw,
+        reader.get_cumsales().call_raw,
+        reader.get_total_sale().call_raw,
+    ];
+    let results = batcher.aggregate(provider, &calls, block_id).await?;
+
+    let price_times = Vec::<u64>::cairo_deserialize(&results[0], 0)?;
+    let prices = u256_span(&results[1])?;
+    let updated_prices = u256_span(&results[2])?;
+    let cumsale_times = Vec::<u64>::cairo_deserialize(&results[3], 0)?;
+    let cumsales = u256_span(&results[4])?;
+    let total_sale = u256_to_u128(&U256::cairo_deserialize(&results[5], 0)?)?;
+    println!(
+        "{} prices, {} cumulative sales, total sale ${}",
+        prices.len(),
+        cumsales.len(),
+        total_sale / 1_000_000
+    );
+
+    let depositors = yielder_depositors(provider, yielder, block_id).await?;
+
+    let mut calls = vec![];
+    for depositor in depositors.iter() {
+        let account = ContractAddress(*depositor);
+        calls.push(reader.get_sale_of(&account).call_raw);
+        calls.push(reader.get_apr(&account).call_raw);
+    }
+    println!("Fetching {} depositors...", depositors.len());
+    let results = batcher.aggregate(provider, &calls, block_id).await?;
+
+    let mut sales = BTreeMap::new();
+    let mut total_depositor_sale = 0;
+    for (depositor, data) in depositors.iter().zip(results.chunks(2)) {
+        let sale = u256_to_u128(&U256::cairo_deserialize(&data[0], 0)?)?;
+        // (numerator, denominator)
+        let apr = <(U256, U256)>::cairo_deserialize(&data[1], 0)?;
+        let apr = (u256_to_u128(&apr.0)?, u256_to_u128(&apr.1)?);
+        total_depositor_sale += sale;
+        if sale == 0 {
+            continue;
+        }
+        sales.insert(
+            depositor.to_hex_string(),
+            json!({
+                "sale": sale.to_string(),
+                "share_ppm": share_ppm(sale, total_sale).to_string(),
+                "apr": { "numerator": apr.0.to_string(), "denominator": apr.1.to_string() },
+            }),
+        );
+    }
+    println!(
+        "Depositor sales for {} are ${} out of ${}",
+        name,
+        total_depositor_sale / 1_000_000,
+        total_sale / 1_000_000
+    );
+    // Realised shares only add up to the whole when every sale is a depositor's
+    let consistent = total_depositor_sale == total_sale;
+    if !consistent {
+        println!(
+            "Depositor sales for {} ({}) do not match get_total_sale ({})",
+            name, total_depositor_sale, total_sale
+        );
+    }
+
+    Ok(json!({
+        "yielder": yielder.to_hex_string(),
+        "prices": series("prices", &price_times, &prices)?,
+        "updated_prices": series("updated_prices", &price_times, &updated_prices)?,
+        "cumsales": series("cumsales", &cumsale_times, &cumsales)?,
+        "total_sale": total_sale.to_string(),
+        "depositors_sale": total_depositor_sale.to_string(),
+        "consistent": consistent,
+        "depositors": sales,
+    }))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let url = Url::parse(&args.rpc)?;
+    let provider = JsonRpcClient::new(CachedTransport::new(HttpTransport::new(url.clone())).await?);
+    let batcher = Batcher::from_env(CachedTransport::new(HttpTransport::new(url)).await?)?;
+    println!("Provider initialized successfully");
+
+    // Pin every call to the same block so the series are consistent
+    let block_number = match args.block {
+        Some(block) => block,
+        None => provider.block_number().await?,
+    };
+    let block_id = BlockId::Number(block_number);
+    println!("Reading at block {}", block_number);
+
+    let mut history = BTreeMap::new();
+    for (name, yielder) in YIELDERS {
+        history.insert(
+            name,
+            yielder_history(&provider, &batcher, name, yielder, block_id).await?,
+        );
+    }
+
+    let json_output = json!({ "block_number": block_number, "yielders": history });
+    std::fs::write(
+        "output/yielder_history.json",
+        serde_json::to_string_pretty(&json_output)?,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series_rejects_mismatched_lengths() {
+        assert_eq!(series("prices", &[1, 2], &[10, 20]).unwrap().len(), 2);
+        assert!(series("prices", &[1, 2], &[10]).is_err());
+    }
+
+    #[test]
+    fn share_is_in_parts_per_million() {
+        assert_eq!(share_ppm(1, 3), 333_333);
+        assert_eq!(share_ppm(5, 5), 1_000_000);
+        assert_eq!(share_ppm(5, 0), 0);
+    }
+}
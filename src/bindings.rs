@@ -0,0 +1,15 @@
+//! Typed contract bindings generated with `abigen!` from the ABIs that `fetch-abis --bindings`
+//! writes, one module per ABI so their types never collide. Every call exposes `call_raw`
+//! for batching.
+
+pub mod yielder {
+    cainome::rs::abigen!(Yielder, "./yielder.abi.json");
+}
+
+pub mod project {
+    cainome::rs::abigen!(Project, "./project.abi.json");
+}
+
+pub mod offsetter {
+    cainome::rs::abigen!(Offsetter, "./offsetter.abi.json");
+}
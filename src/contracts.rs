@@ -0,0 +1,82 @@
+//! Mainnet addresses of the contracts `scan`, `tonnes`, `yielder-history`, `v1`, `v2`,
+//! `farming` and `fetch-abis` read.
+
+use starknet::{core::types::Felt, macros::felt};
+
+/// ERC-3525 project of Banegas Farm (slot 1), Las Delicias (2) and Manjarisoa (3)
+pub const PROJECT_V2: Felt =
+    felt!("0x0516d0acb6341dcc567e85dc90c8f64e0c33d3daba0a310157d6bba0656c8769");
+pub const KARATHURU_V2: Felt =
+    felt!("0x05a667adc04676fba78a29371561a0bf91dab25847d5dc4709a93a4cfb5ff293");
+
+pub const BANEGAS_YIELDER: Felt =
+    felt!("0x03d25473be5a6316f351e8f964d0c303357c006f7107779f648d9879b7c6d58a");
+pub const BANEGAS_OFFSETTER: Felt =
+    felt!("0x0324b531f731100b494e2f978a26b20b5870585dd96d9f1166b43a28ebbb8aba");
+pub const LAS_DELICIAS_YIELDER: Felt =
+    felt!("0x00426d4e86913759bcc49b7f992b1fe62e6571e8f8089c23d95fea815dbad471");
+pub const LAS_DELICIAS_OFFSETTER: Felt =
+    felt!("0x022f40128af9798a0b734874fd993bbab6cf75845f26f844cb151b7041132c6d");
+pub const MANJARISOA_YIELDER: Felt =
+    felt!("0x03afe61732ed9b226309775ac4705129319729d3bee81da5632146ffd72652ae");
+pub const MANJARISOA_OFFSETTER: Felt =
+    felt!("0x04258037980fcc15083cde324abe1861ac00d4d48b7d60d76b5efd6f57e59e73");
+
+/// v1 ERC-721 collections
+pub const BANEGAS_V1: Felt =
+    felt!("0x04047810e4f759336f941a16b6de9d8d2f934e976b9a9431a2964646df9025c6");
+pub const LAS_DELICIAS_V1: Felt =
+    felt!("0x00ebf4bbab9c934fa0212c9358331d4a9543ad66a7e0007d4a720cfa6b56061e");
+pub const MANJARISO_BRONZE_V1: Felt =
+    felt!("0x0541b5dd5fae206ceccaf4eeb0642e4c04d456c5bc296eab047c9414bdad4f09");
+pub const MANJARISO_SILVER_V1: Felt =
+    felt!("0x06191013bbd6bcf11d69f3d60d20f7aa03439d7011bd511d13122de2275dce21");
+pub const MANJARISO_GOLD_V1: Felt =
+    felt!("0x061bcc33b0469cd072ad47813a1efd250fd36a28425d774c31c8f33c87306e8e");
+
+/// Collections `v1` snapshots by default.
+pub const V1_COLLECTIONS: [Felt; 5] = [
+    BANEGAS_V1,
+    LAS_DELICIAS_V1,
+    MANJARISO_BRONZE_V1,
+    MANJARISO_SILVER_V1,
+    MANJARISO_GOLD_V1,
+];
+
+/// Projects `v2` snapshots by default.
+pub const V2_PROJECTS: [Felt; 2] = [PROJECT_V2, KARATHURU_V2];
+
+/// Farming contracts `farming` totals the deposits of.
+pub const FARMS: [Felt; 6] = [
+    BANEGAS_YIELDER,
+    BANEGAS_OFFSETTER,
+    LAS_DELICIAS_YIELDER,
+    LAS_DELICIAS_OFFSETTER,
+    MANJARISOA_YIELDER,
+    MANJARISOA_OFFSETTER,
+];
+
+/// Every contract above by name, as `fetch-abis` caches them.
+pub const CONTRACTS: [(&str, Felt); 13] = [
+    ("ProjectV2", PROJECT_V2),
+    ("KarathuruV2", KARATHURU_V2),
+    ("BanegasFarmYielder", BANEGAS_YIELDER),
+    ("BanegasFarmOffsetter", BANEGAS_OFFSETTER),
+    ("LasDeliciasYielder", LAS_DELICIAS_YIELDER),
+    ("LasDeliciasOffsetter", LAS_DELICIAS_OFFSETTER),
+    ("ManjarisoaYielder", MANJARISOA_YIELDER),
+    ("ManjarisoaOffsetter", MANJARISOA_OFFSETTER),
+    ("BanegasFarmV1", BANEGAS_V1),
+    ("LasDeliciasV1", LAS_DELICIAS_V1),
+    ("ManjarisoBronzeV1", MANJARISO_BRONZE_V1),
+    ("ManjarisoSilverV1", MANJARISO_SILVER_V1),
+    ("ManjarisoGoldV1", MANJARISO_GOLD_V1),
+];
+
+/// Address of the contract named `name` in [`CONTRACTS`].
+pub fn by_name(name: &str) -> Option<Felt> {
+    CONTRACTS
+        .iter()
+        .find(|(contract, _)| *contract == name)
+        .map(|(_, address)| *address)
+}
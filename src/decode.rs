@@ -0,0 +1,126 @@
+use cainome::cairo_serde::{CairoSerde, U256};
+use starknet::core::types::Felt;
+
+/// Value of a `u256` that must fit in its low word, failing rather than dropping the high one.
+pub fn u256_to_u128(value: &U256) -> Result<u128, String> {
+    match value.high {
+        0 => Ok(value.low),
+        high => Err(format!("u256 ({}, {}) overflows u128", value.low, high)),
+    }
+}
+
+/// Felt of a `u256` token id or slot, as keyed in the snapshots.
+pub fn u256_to_felt(value: &U256) -> Felt {
+    Felt::from_bytes_be(&value.to_bytes_be())
+}
+
+/// `(from, to, amount or token id)` of an ERC-20 or ERC-721 `Transfer` event.
+/// Cairo 0 contracts emit everything as data, Cairo 1 contracts key `from` and `to`,
+/// and the token id of an ERC-721.
+pub fn decode_transfer(keys: &[Felt], data: &[Felt]) -> Option<(Felt, Felt, U256)> {
+    let (from, to, low, high) = match (keys, data) {
+        ([_], [from, to, low, high])
+        | ([_, from, to], [low, high])
+        | ([_, from, to, low, high], []) => (from, to, low, high),
+        _ => return None,
+    };
+    let value = U256::cairo_deserialize(&[*low, *high], 0).ok()?;
+    Some((*from, *to, value))
+}
+
+/// Decodes a string returned as a `ByteArray`, a legacy felt array or a single short string.
+pub fn decode_string(data: &[Felt]) -> Option<String> {
+    let len = |felt: &Felt| felt.to_bigint().to_string().parse::<usize>().ok();
+    let bytes: Vec<u8> = match data {
+        [] => return None,
+        [short] => string_bytes(short, 31),
+        // ByteArray: [full_words_len, full_words (31 bytes)..., pending_word, pending_len]
+        [words_len, .., pending, pending_len]
+            if len(words_len).map(|l| l + 3) == Some(data.len())
+                && len(pending_len).is_some_and(|l| l < 31) =>
+        {
+            let mut bytes: Vec<u8> = data[1..data.len() - 2]
+                .iter()
+                .flat_map(|word| string_bytes(word, 31))
+                .collect();
+            bytes.extend(string_bytes(pending, len(pending_len)?));
+            bytes
+        }
+        // Legacy felt array: [len, short strings...]
+        [array_len, ..] if len(array_len).map(|l| l + 1) == Some(data.len()) => data[1..]
+            .iter()
+            .flat_map(|felt| string_bytes(felt, 31))
+            .filter(|b| *b != 0)
+            .collect(),
+        _ => return None,
+    };
+    Some(
+        String::from_utf8_lossy(&bytes)
+            .trim_matches('\0')
+            .to_string(),
+    )
+}
+
+/// The last `len` bytes of a felt, strings are right aligned.
+fn string_bytes(felt: &Felt, len: usize) -> Vec<u8> {
+    felt.to_bytes_be()[32 - len..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::core::utils::cairo_short_string_to_felt;
+
+    #[test]
+    fn u256_to_u128_rejects_a_high_word() {
+        assert_eq!(u256_to_u128(&U256 { low: 42, high: 0 }), Ok(42));
+        assert!(u256_to_u128(&U256 { low: 42, high: 1 }).is_err());
+    }
+
+    #[test]
+    fn u256_to_felt_keeps_both_words() {
+        assert_eq!(u256_to_felt(&U256 { low: 42, high: 0 }), Felt::from(42));
+        let high = U256 { low: 1, high: 1 };
+        assert_eq!(u256_to_felt(&high), Felt::from(u128::MAX) + Felt::TWO);
+    }
+
+    #[test]
+    fn decode_transfer_reads_cairo_0_and_cairo_1_layouts() {
+        let transfer = Felt::from(0x99);
+        let (from, to) = (Felt::ONE, Felt::TWO);
+        let value = U256 { low: 7, high: 0 };
+        let cairo_0 = [from, to, Felt::from(7), Felt::ZERO];
+        assert_eq!(
+            decode_transfer(&[transfer], &cairo_0),
+            Some((from, to, value))
+        );
+        let erc20 = [Felt::from(7), Felt::ZERO];
+        assert_eq!(
+            decode_transfer(&[transfer, from, to], &erc20),
+            Some((from, to, value))
+        );
+        let erc721 = [transfer, from, to, Felt::from(7), Felt::ZERO];
+        assert_eq!(decode_transfer(&erc721, &[]), Some((from, to, value)));
+        assert_eq!(decode_transfer(&[transfer], &[from, to]), None);
+    }
+
+    #[test]
+    fn decode_string_reads_every_string_layout() {
+        let short = |s: &str| cairo_short_string_to_felt(s).unwrap();
+        assert_eq!(
+            decode_string(&[short("Banegas")]),
+            Some("Banegas".to_string())
+        );
+        let legacy = [Felt::TWO, short("Carbon"), short("able")];
+        assert_eq!(decode_string(&legacy), Some("Carbonable".to_string()));
+
+        let byte_array = [Felt::ZERO, short("hello"), Felt::from(5)];
+        assert_eq!(decode_string(&byte_array), Some("hello".to_string()));
+        let word = "Carbonable Banegas Farm, Costa ";
+        let long = [Felt::ONE, short(word), short("Rica"), Felt::from(4)];
+        assert_eq!(decode_string(&long), Some(word.to_string() + "Rica"));
+
+        assert_eq!(decode_string(&[]), None);
+        assert_eq!(decode_string(&[Felt::from(5), Felt::ONE]), None);
+    }
+}
@@ -0,0 +1,376 @@
+//! Entrypoints read from the class of a contract, rather than guessed from the
+//! error message of a failed call.
+
+use crate::rpc::{Batcher, RATE_LIMIT};
+use starknet::{
+    core::{
+        types::{BlockId, ContractClass, Felt, FunctionCall, StarknetError},
+        utils::get_selector_from_name,
+    },
+    macros::selector,
+    providers::{Provider, ProviderError},
+};
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
+    error::Error,
+};
+use tokio::time::sleep;
+
+/// JSON-RPC error code of `ContractNotFound`.
+const CONTRACT_NOT_FOUND: i64 = 20;
+
+/// Why a call failed.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallFailure {
+    /// No contract is deployed at the address
+    NotDeployed,
+    /// The class of the contract does not list the entrypoint
+    EntrypointNotFound,
+    /// The call reverted
+    Reverted,
+    /// The node did not answer, e.g. a rate limit or a timeout, the outcome is unknown
+    Unanswered,
+}
+
+/// Failure of a call the node answered, `None` for transport and node errors,
+/// which callers either retry, fail on or record as `Unanswered`.
+pub fn classify(error: &ProviderError) -> Option<CallFailure> {
+    match error {
+        ProviderError::StarknetError(StarknetError::ContractNotFound) => {
+            Some(CallFailure::NotDeployed)
+        }
+        ProviderError::StarknetError(StarknetError::ContractError(_)) => {
+            Some(CallFailure::Reverted)
+        }
+        _ => None,
+    }
+}
+
+/// `ownerOf` for `owner_of` and the other way around.
+pub fn alternate_name(name: &str) -> String {
+    let mut alternate = String::new();
+    if name.contains('_') {
+        for (i, part) in name.split('_').enumerate() {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) if i > 0 => {
+                    alternate.push(first.to_ascii_uppercase());
+                    alternate.push_str(chars.as_str());
+                }
+                _ => alternate.push_str(part),
+            }
+        }
+    } else {
+        // acronyms stay one word: `tokenURI` is `token_uri`
+        let mut previous_lowercase = false;
+        for c in name.chars() {
+            if c.is_ascii_uppercase() && previous_lowercase {
+                alternate.push('_');
+            }
+            previous_lowercase = c.is_ascii_lowercase();
+            alternate.push(c.to_ascii_lowercase());
+        }
+    }
+    alternate
+}
+
+/// External entrypoints of a contract.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entrypoints {
+    /// Cairo 0 class with a `__default__` entrypoint, e.g. a proxy, which forwards
+    /// the selectors it does not list to its implementation
+    Forwarding,
+    Listed(HashSet<Felt>),
+}
+
+impl Entrypoints {
+    pub fn of(class: &ContractClass) -> Self {
+        let selectors: HashSet<Felt> = match class {
+            ContractClass::Sierra(class) => class
+                .entry_points_by_type
+                .external
+                .iter()
+                .map(|entrypoint| entrypoint.selector)
+                .collect(),
+            ContractClass::Legacy(class) => class
+                .entry_points_by_type
+                .external
+                .iter()
+                .map(|entrypoint| entrypoint.selector)
+                .collect(),
+        };
+        if selectors.contains(&selector!("__default__")) {
+            Entrypoints::Forwarding
+        } else {
+            Entrypoints::Listed(selectors)
+        }
+    }
+
+    /// Selectors to call for `name`: the one of `name` or of its alternate naming
+    /// the class lists, or both in turn for a forwarding class.
+    pub fn candidates(&self, name: &str) -> Result<Vec<Felt>, CallFailure> {
+        let names = [
+            get_selector_from_name(name).ok(),
+            get_selector_from_name(&alternate_name(name)).ok(),
+        ];
+        match self {
+            Entrypoints::Forwarding => Ok(names.into_iter().flatten().collect()),
+            Entrypoints::Listed(selectors) => names
+                .into_iter()
+                .flatten()
+                .find(|selector| selectors.contains(selector))
+                .map(|selector| vec![selector])
+                .ok_or(CallFailure::EntrypointNotFound),
+        }
+    }
+}
+
+/// Selectors resolved per class hash. Cairo 0 contracts use camelCase names and
+/// Cairo 1 contracts snake_case, the naming the class lists is used. A forwarding
+/// class lists neither, both are tried and the one that answered is remembered.
+#[derive(Debug, Default)]
+pub struct Selectors {
+    class_hashes: HashMap<Felt, Option<Felt>>,
+    entrypoints: HashMap<Felt, Entrypoints>,
+    resolved: HashMap<(Felt, String), Felt>,
+}
+
+impl Selectors {
+    /// Class hash of `contract`, `None` when nothing is deployed there.
+    async fn class_hash(
+        &mut self,
+        provider: &impl Provider,
+        contract: Felt,
+        block_id: BlockId,
+    ) -> Result<Option<Felt>, ProviderError> {
+        if let Some(class_hash) = self.class_hashes.get(&contract) {
+            return Ok(*class_hash);
+        }
+        let class_hash = match provider.get_class_hash_at(block_id, contract).await {
+            Ok(class_hash) => Some(class_hash),
+            Err(ProviderError::StarknetError(StarknetError::ContractNotFound)) => None,
+            Err(e) => return Err(e),
+        };
+        sleep(RATE_LIMIT).await; // Rate limit delay
+        self.class_hashes.insert(contract, class_hash);
+        Ok(class_hash)
+    }
+
+    /// Reads the class hash of every contract of `contracts` and the class of every new
+    /// class hash in JSON-RPC batches, so resolving their selectors makes no request.
+    /// A contract the node did not answer for is left to be read alone.
+    pub async fn prefetch(
+        &mut self,
+        batcher: &Batcher,
+        contracts: &[Felt],
+        block_id: BlockId,
+    ) -> Result<(), Box<dyn Error>> {
+        let contracts: Vec<Felt> = contracts
+            .iter()
+            .filter(|contract| !self.class_hashes.contains_key(contract))
+            .cloned()
+            .collect::<BTreeSet<Felt>>()
+            .into_iter()
+            .collect();
+        for (contract, result) in contracts
+            .iter()
+            .zip(batcher.try_class_hashes(&contracts, block_id).await?)
+        {
+            match result {
+                Ok(class_hash) => {
+                    self.class_hashes.insert(*contract, Some(class_hash));
+                }
+                Err(error) if error.code == CONTRACT_NOT_FOUND => {
+                    self.class_hashes.insert(*contract, None);
+                }
+                Err(_) => {}
+            }
+        }
+
+        let class_hashes: Vec<Felt> = self
+            .class_hashes
+            .values()
+            .flatten()
+            .filter(|class_hash| !self.entrypoints.contains_key(class_hash))
+            .cloned()
+            .collect::<BTreeSet<Felt>>()
+            .into_iter()
+            .collect();
+        for (class_hash, result) in class_hashes
+            .iter()
+            .zip(batcher.try_classes(&class_hashes, block_id).await?)
+        {
+            if let Ok(class) = result {
+                self.entrypoints
+                    .insert(*class_hash, Entrypoints::of(&class));
+            }
+        }
+        Ok(())
+    }
+
+    /// Selectors to call for `name` on `contract`, see [`Entrypoints::candidates`].
+    async fn candidates(
+        &mut self,
+        provider: &impl Provider,
+        contract: Felt,
+        name: &str,
+        block_id: BlockId,
+    ) -> Result<(Option<Felt>, Result<Vec<Felt>, CallFailure>), ProviderError> {
+        let Some(class_hash) = self.class_hash(provider, contract, block_id).await? else {
+            return Ok((None, Err(CallFailure::NotDeployed)));
+        };
+        if let Some(selector) = self.resolved.get(&(class_hash, name.to_string())) {
+            return Ok((Some(class_hash), Ok(vec![*selector])));
+        }
+        if let Entry::Vacant(entry) = self.entrypoints.entry(class_hash) {
+            let class = provider.get_class(block_id, class_hash).await?;
+            sleep(RATE_LIMIT).await; // Rate limit delay
+            entry.insert(Entrypoints::of(&class));
+        }
+        Ok((
+            Some(class_hash),
+            self.entrypoints[&class_hash].candidates(name),
+        ))
+    }
+
+    /// Selector to call for `name` on `contract` without probing it, the name as
+    /// given for a forwarding class.
+    pub async fn selector(
+        &mut self,
+        provider: &impl Provider,
+        contract: Felt,
+        name: &str,
+        block_id: BlockId,
+    ) -> Result<Result<Felt, CallFailure>, ProviderError> {
+        let (_, candidates) = self.candidates(provider, contract, name, block_id).await?;
+        Ok(candidates.map(|candidates| candidates[0]))
+    }
+
+    /// Calls `name` on `contract` with the selector its class lists.
+    pub async fn call(
+        &mut self,
+        provider: &impl Provider,
+        contract: Felt,
+        name: &str,
+        calldata: Vec<Felt>,
+        block_id: BlockId,
+    ) -> Result<Vec<Felt>, Box<dyn Error>> {
+        Ok(self
+            .probe(provider, contract, name, calldata, block_id)
+            .await?
+            .1)
+    }
+
+    /// Selector of `name` on `contract`, probing it with `calldata` for a forwarding class.
+    pub async fn resolve(
+        &mut self,
+        provider: &impl Provider,
+        contract: Felt,
+        name: &str,
+        calldata: Vec<Felt>,
+        block_id: BlockId,
+    ) -> Result<Felt, Box<dyn Error>> {
+        Ok(self
+            .probe(provider, contract, name, calldata, block_id)
+            .await?
+            .0)
+    }
+
+    async fn probe(
+        &mut self,
+        provider: &impl Provider,
+        contract: Felt,
+        name: &str,
+        calldata: Vec<Felt>,
+        block_id: BlockId,
+    ) -> Result<(Felt, Vec<Felt>), Box<dyn Error>> {
+        let (class_hash, candidates) = self.candidates(provider, contract, name, block_id).await?;
+        let candidates = candidates
+            .map_err(|failure| format!("{}: {} {:?}", contract.to_hex_string(), name, failure))?;
+
+        let mut last_error = None;
+        for entry_point_selector in candidates {
+            let result = provider
+                .call(
+                    FunctionCall {
+                        contract_address: contract,
+                        entry_point_selector,
+                        calldata: calldata.clone(),
+                    },
+                    block_id,
+                )
+                .await;
+            sleep(RATE_LIMIT).await; // Rate limit delay
+            match result {
+                Ok(result) => {
+                    if let Some(class_hash) = class_hash {
+                        self.resolved
+                            .insert((class_hash, name.to_string()), entry_point_selector);
+                    }
+                    return Ok((entry_point_selector, result));
+                }
+                // A forwarding class reverts on the naming its implementation does not use
+                Err(e) if classify(&e) == Some(CallFailure::Reverted) => last_error = Some(e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(format!(
+            "{}: {} failed: {}",
+            contract.to_hex_string(),
+            name,
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternate_names_switch_case_convention() {
+        assert_eq!(alternate_name("ownerOf"), "owner_of");
+        assert_eq!(alternate_name("owner_of"), "ownerOf");
+        assert_eq!(alternate_name("tokenURI"), "token_uri");
+        assert_eq!(alternate_name("totalSupply"), "total_supply");
+        assert_eq!(alternate_name("name"), "name");
+    }
+
+    #[test]
+    fn candidates_follow_the_class() {
+        let listed = Entrypoints::Listed(HashSet::from([selector!("owner_of")]));
+        assert_eq!(
+            listed.candidates("ownerOf"),
+            Ok(vec![selector!("owner_of")])
+        );
+        assert_eq!(
+            listed.candidates("owner_of"),
+            Ok(vec![selector!("owner_of")])
+        );
+        assert_eq!(
+            listed.candidates("supportsInterface"),
+            Err(CallFailure::EntrypointNotFound)
+        );
+        assert_eq!(
+            Entrypoints::Forwarding.candidates("ownerOf"),
+            Ok(vec![selector!("ownerOf"), selector!("owner_of")])
+        );
+    }
+
+    #[test]
+    fn node_errors_are_not_call_failures() {
+        assert_eq!(
+            classify(&ProviderError::StarknetError(
+                StarknetError::ContractNotFound
+            )),
+            Some(CallFailure::NotDeployed)
+        );
+        assert_eq!(classify(&ProviderError::RateLimited), None);
+        assert_eq!(
+            classify(&ProviderError::StarknetError(StarknetError::BlockNotFound)),
+            None
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! ERC-3525 scanner shared by `scan` and `v2`: discovers the slots of a contract
+//! and enumerates the tokens of each through the [`Batcher`].
+
+use crate::{
+    bindings::project::ProjectReader,
+    decode::{u256_to_felt, u256_to_u128},
+    rpc::{Batcher, CachedTransport},
+};
+use cainome::cairo_serde::{CairoSerde, ContractAddress, U256};
+use starknet::{
+    core::types::{BlockId, Felt, FunctionCall},
+    providers::jsonrpc::JsonRpcClient,
+};
+use std::{collections::HashSet, error::Error};
+
+/// Token of a slot at the pinned block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotToken {
+    pub id: U256,
+    pub owner: Felt,
+    pub value: u128,
+    /// Address approved for the token, `0x0` when none
+    pub approved: Felt,
+}
+
+/// Every slot of `contract`, from `slot_count` and `slot_by_index`.
+pub async fn slots(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    contract: Felt,
+    block_id: BlockId,
+) -> Result<Vec<U256>, Box<dyn Error>> {
+    let reader = ProjectReader::new(contract, provider);
+    let slot_count = u256_to_u128(&reader.slot_count().block_id(block_id).call().await?)?;
+    println!(
+        "Total slots for {}: {}",
+        contract.to_hex_string(),
+        slot_count
+    );
+
+    let calls: Vec<FunctionCall> = (0..slot_count)
+        .map(|index| {
+            reader
+                .slot_by_index(&U256 {
+                    low: index,
+                    high: 0,
+                })
+                .call_raw
+        })
+        .collect();
+    let mut slots = vec![];
+    for data in batcher.aggregate(provider, &calls, block_id).await? {
+        slots.push(U256::cairo_deserialize(&data, 0)?);
+    }
+    Ok(slots)
+}
+
+/// Token ids of `slot`, from `token_supply_in_slot` and `token_in_slot_by_index`.
+/// Fails unless they are as many distinct ids as the slot supply.
+pub async fn slot_token_ids(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    contract: Felt,
+    slot: &U256,
+    block_id: BlockId,
+) -> Result<Vec<U256>, Box<dyn Error>> {
+    let reader = ProjectReader::new(contract, provider);
+    let slot_supply = u256_to_u128(
+        &reader
+            .token_supply_in_slot(slot)
+            .block_id(block_id)
+            .call()
+            .await?,
+    )?;
+    println!("Slot {} supply: {}", u256_to_felt(slot), slot_supply);
+
+    let calls: Vec<FunctionCall> = (0..slot_supply)
+        .map(|index| {
+            reader
+                .token_in_slot_by_index(
+                    slot,
+                    &U256 {
+                        low: index,
+                        high: 0,
+                    },
+                )
+                .call_raw
+        })
+        .collect();
+    println!("Fetching token ids...");
+    let mut token_ids = vec![];
+    for data in batcher.aggregate(provider, &calls, block_id).await? {
+        token_ids.push(U256::cairo_deserialize(&data, 0)?);
+    }
+
+    let unique: HashSet<Felt> = token_ids.iter().map(u256_to_felt).collect();
+    if token_ids.len() as u128 != slot_supply || unique.len() as u128 != slot_supply {
+        return Err(format!(
+            "slot {}: enumerated {} tokens ({} distinct) but slot supply is {}",
+            u256_to_felt(slot),
+            token_ids.len(),
+            unique.len(),
+            slot_supply
+        )
+        .into());
+    }
+    Ok(token_ids)
+}
+
+/// Owner, value and approval of every token of `token_ids`, in order.
+pub async fn read_tokens(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    contract: Felt,
+    token_ids: &[U256],
+    block_id: BlockId,
+) -> Result<Vec<SlotToken>, Box<dyn Error>> {
+    let reader = ProjectReader::new(contract, provider);
+    let calls: Vec<FunctionCall> = token_ids
+        .iter()
+        .flat_map(|id| {
+            [
+                reader.owner_of(id).call_raw,
+                reader.value_of(id).call_raw,
+                reader.get_approved(id).call_raw,
+            ]
+        })
+        .collect();
+    println!("Fetching token data...");
+    let results = batcher.aggregate(provider, &calls, block_id).await?;
+
+    let mut tokens = vec![];
+    for (id, data) in token_ids.iter().zip(results.chunks(3)) {
+        tokens.push(SlotToken {
+            id: *id,
+            owner: ContractAddress::cairo_deserialize(&data[0], 0)?.0,
+            value: u256_to_u128(&U256::cairo_deserialize(&data[1], 0)?)?,
+            approved: ContractAddress::cairo_deserialize(&data[2], 0)?.0,
+        });
+    }
+    Ok(tokens)
+}
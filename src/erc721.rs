@@ -0,0 +1,200 @@
+//! ERC-721 enumeration shared by `scan` and `v1`: lists the live token ids of a
+//! v1 collection, from `tokenByIndex` or by replaying its `Transfer` events.
+
+use crate::{
+    decode::{decode_transfer, u256_to_felt, u256_to_u128},
+    entrypoints::{CallFailure, Selectors},
+    rpc::{events_of, Batcher, CachedTransport},
+};
+use cainome::cairo_serde::{CairoSerde, U256};
+use starknet::{
+    core::types::{BlockId, Felt, FunctionCall},
+    macros::selector,
+    providers::jsonrpc::JsonRpcClient,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+};
+
+/// How the live token ids were found.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Enumeration {
+    /// `totalSupply` and `tokenByIndex`
+    Enumerable,
+    /// Replayed `Transfer` events, burns excluded
+    TransferEvents,
+}
+
+/// A decoded `Transfer` event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub transaction_hash: Felt,
+    pub from: Felt,
+    pub to: Felt,
+    pub token_id: Felt,
+}
+
+/// Live token ids of `contract` at `block_id`, enumerated when its class lists
+/// `totalSupply` and `tokenByIndex`, replayed from `Transfer` events otherwise.
+pub async fn token_ids(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    selectors: &mut Selectors,
+    contract: Felt,
+    block_id: BlockId,
+) -> Result<(Enumeration, Vec<Felt>), Box<dyn Error>> {
+    match enumerate_tokens(provider, batcher, selectors, contract, block_id).await? {
+        Some(token_ids) => Ok((Enumeration::Enumerable, token_ids)),
+        None => Ok((
+            Enumeration::TransferEvents,
+            replay_transfers(provider, contract, block_id).await?,
+        )),
+    }
+}
+
+/// Token ids from `totalSupply` and `tokenByIndex`, `None` when the class of the
+/// contract does not list them. Any other failure is an error, a node that did not
+/// answer must not switch the snapshot to event replay.
+pub async fn enumerate_tokens(
+    provider: &JsonRpcClient<CachedTransport>,
+    batcher: &Batcher,
+    selectors: &mut Selectors,
+    contract: Felt,
+    block_id: BlockId,
+) -> Result<Option<Vec<Felt>>, Box<dyn Error>> {
+    for name in ["totalSupply", "tokenByIndex"] {
+        match selectors
+            .selector(provider, contract, name, block_id)
+            .await?
+        {
+            Ok(_) => {}
+            Err(CallFailure::EntrypointNotFound) => {
+                println!("No {}, falling back to Transfer events", name);
+                return Ok(None);
+            }
+            Err(failure) => {
+                return Err(format!("{}: {} {:?}", contract.to_hex_string(), name, failure).into())
+            }
+        }
+    }
+
+    let total_supply_call_result = selectors
+        .call(provider, contract, "totalSupply", vec![], block_id)
+        .await?;
+    let total_supply = u256_to_u128(&U256::cairo_deserialize(&total_supply_call_result, 0)?)
+        .map_err(|e| format!("{} totalSupply: {}", contract.to_hex_string(), e))?;
+    let total_supply = usize::try_from(total_supply)?;
+    println!("Total supply: {}", total_supply);
+    if total_supply == 0 {
+        return Ok(Some(vec![]));
+    }
+
+    let probe = vec![Felt::ZERO, Felt::ZERO];
+    let token_by_index = selectors
+        .resolve(provider, contract, "tokenByIndex", probe, block_id)
+        .await?;
+    let calls: Vec<FunctionCall> = (0..total_supply)
+        .map(|i| FunctionCall {
+            contract_address: contract,
+            entry_point_selector: token_by_index,
+            calldata: vec![Felt::from(i), Felt::ZERO],
+        })
+        .collect();
+    let mut token_ids = vec![];
+    for data in batcher.aggregate(provider, &calls, block_id).await? {
+        token_ids.push(u256_to_felt(&U256::cairo_deserialize(&data, 0)?));
+    }
+
+    let unique: HashSet<&Felt> = token_ids.iter().collect();
+    if token_ids.len() != total_supply || unique.len() != total_supply {
+        return Err(format!(
+            "{}: enumerated {} tokens ({} distinct) but total supply is {}",
+            contract.to_hex_string(),
+            token_ids.len(),
+            unique.len(),
+            total_supply
+        )
+        .into());
+    }
+    Ok(Some(token_ids))
+}
+
+/// Every `Transfer` of `contract` from genesis to `block_id`, in event order.
+pub async fn transfers(
+    provider: &JsonRpcClient<CachedTransport>,
+    contract: Felt,
+    block_id: BlockId,
+) -> Result<Vec<Transfer>, Box<dyn Error>> {
+    let mut transfers = vec![];
+    for event in events_of(provider, contract, selector!("Transfer"), block_id).await? {
+        let (from, to, token_id) = decode_transfer(&event.keys, &event.data).ok_or(format!(
+            "unexpected Transfer layout in {}",
+            event.transaction_hash.to_hex_string()
+        ))?;
+        transfers.push(Transfer {
+            transaction_hash: event.transaction_hash,
+            from,
+            to,
+            token_id: u256_to_felt(&token_id),
+        });
+    }
+    Ok(transfers)
+}
+
+/// Token ids still alive at `block_id`, replaying every `Transfer` from genesis.
+pub async fn replay_transfers(
+    provider: &JsonRpcClient<CachedTransport>,
+    contract: Felt,
+    block_id: BlockId,
+) -> Result<Vec<Felt>, Box<dyn Error>> {
+    let transfers = transfers(provider, contract, block_id).await?;
+    let owners = live_tokens(&transfers);
+    println!(
+        "Replayed {} Transfer events, {} live tokens",
+        transfers.len(),
+        owners.len()
+    );
+    Ok(owners.into_keys().collect())
+}
+
+/// Token id -> current owner after `transfers`, burnt tokens removed.
+fn live_tokens(transfers: &[Transfer]) -> BTreeMap<Felt, Felt> {
+    let mut owners = BTreeMap::new();
+    for transfer in transfers {
+        if transfer.to == Felt::ZERO {
+            owners.remove(&transfer.token_id);
+        } else {
+            owners.insert(transfer.token_id, transfer.to);
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(from: u64, to: u64, token_id: u64) -> Transfer {
+        Transfer {
+            transaction_hash: Felt::ZERO,
+            from: Felt::from(from),
+            to: Felt::from(to),
+            token_id: Felt::from(token_id),
+        }
+    }
+
+    #[test]
+    fn replay_keeps_the_last_owner_and_drops_burns() {
+        let transfers = [
+            transfer(0, 1, 1),
+            transfer(0, 1, 2),
+            transfer(1, 2, 1),
+            transfer(1, 0, 2),
+        ];
+        let owners = live_tokens(&transfers);
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[&Felt::ONE], Felt::TWO);
+    }
+}
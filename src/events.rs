@@ -0,0 +1,98 @@
+use serde_json::Value;
+
+/// Publishes events to NATS when `NATS_URL` is set, on `<prefix>.<event>`.
+/// Each binary reads its prefix from its own variable so their subjects never collide.
+pub struct Publisher {
+    client: Option<async_nats::Client>,
+    prefix: String,
+}
+
+impl Publisher {
+    /// Connects to `NATS_URL` when set, with the prefix from `prefix_var` or `default_prefix`.
+    pub async fn from_env(
+        prefix_var: &str,
+        default_prefix: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = match std::env::var("NATS_URL") {
+            Ok(url) => Some(async_nats::connect(url).await?),
+            Err(_) => None,
+        };
+        let prefix = std::env::var(prefix_var).unwrap_or(default_prefix.to_string());
+        Ok(Publisher::new(client, &prefix))
+    }
+
+    pub fn new(client: Option<async_nats::Client>, prefix: &str) -> Self {
+        Publisher {
+            client,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    pub fn subject(&self, event: &str) -> String {
+        format!("{}.{}", self.prefix, event)
+    }
+
+    pub async fn publish(&self, event: &str, payload: Value) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        let subject = self.subject(event);
+        if let Err(e) = client
+            .publish(subject.clone(), payload.to_string().into())
+            .await
+        {
+            eprintln!("Error publishing to {}: {}", subject, e);
+        }
+    }
+
+    pub async fn flush(&self) {
+        if let Some(client) = &self.client {
+            if let Err(e) = client.flush().await {
+                eprintln!("Error flushing NATS client: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn publisher_without_client_is_a_no_op() {
+        let publisher = Publisher::new(None, "snapshot");
+        assert_eq!(publisher.subject("meta"), "snapshot.meta");
+        publisher.publish("meta", json!({})).await;
+        publisher.flush().await;
+    }
+
+    /// Run with a local `nats-server` and `cargo test -- --ignored`,
+    /// `NATS_URL` defaults to `nats://127.0.0.1:4222`.
+    #[tokio::test]
+    #[ignore = "needs a local nats-server"]
+    async fn publishes_on_a_local_nats_server() {
+        let url = std::env::var("NATS_URL").unwrap_or("nats://127.0.0.1:4222".to_string());
+        let client = async_nats::connect(url).await.unwrap();
+        let mut subscriber = client.subscribe("test-snapshot.>").await.unwrap();
+        let mut other = client.subscribe("test-allocation.>").await.unwrap();
+
+        let publisher = Publisher::new(Some(client.clone()), "test-snapshot");
+        publisher
+            .publish("project.completed", json!({ "project": "Banegas" }))
+            .await;
+        publisher.flush().await;
+
+        let message = subscriber.next().await.unwrap();
+        assert_eq!(message.subject.as_str(), "test-snapshot.project.completed");
+        let payload: Value = serde_json::from_slice(&message.payload).unwrap();
+        assert_eq!(payload["project"], "Banegas");
+
+        // Nothing leaks to another binary's prefix
+        client.flush().await.unwrap();
+        let leaked =
+            tokio::time::timeout(std::time::Duration::from_millis(200), other.next()).await;
+        assert!(leaked.is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Exclusion rules from `exclusions.json`, applied by `scan` (tokens, slots) and `tonnes` (owners).
+
+use starknet::core::types::Felt;
+use std::{error::Error, fs::File};
+
+/// Every rule must give a reason. A rule whose reason could not be established is
+/// kept `unconfirmed`, listed under "Exclusions to confirm" in `to-check.md`, and
+/// reported each time it applies.
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Exclusion {
+    /// Token of every project of `version` and `contract`, when they are set
+    Token {
+        version: Option<String>,
+        contract: Option<Felt>,
+        token_id: String,
+        reason: String,
+        #[serde(default)]
+        unconfirmed: bool,
+    },
+    /// Whole v2 slot
+    Slot {
+        contract: Felt,
+        slot: String,
+        reason: String,
+        #[serde(default)]
+        unconfirmed: bool,
+    },
+    /// Owner of every project, or of `project` when it is set
+    Owner {
+        project: Option<String>,
+        owner: Felt,
+        reason: String,
+        #[serde(default)]
+        unconfirmed: bool,
+    },
+}
+
+impl Exclusion {
+    pub fn reason(&self) -> &str {
+        let (Exclusion::Token { reason, .. }
+        | Exclusion::Slot { reason, .. }
+        | Exclusion::Owner { reason, .. }) = self;
+        reason
+    }
+
+    pub fn unconfirmed(&self) -> bool {
+        let (Exclusion::Token { unconfirmed, .. }
+        | Exclusion::Slot { unconfirmed, .. }
+        | Exclusion::Owner { unconfirmed, .. }) = self;
+        *unconfirmed
+    }
+}
+
+pub fn validate(exclusions: &[Exclusion]) -> Result<(), String> {
+    match exclusions.iter().find(|e| e.reason().trim().is_empty()) {
+        Some(exclusion) => Err(format!("exclusion without a reason: {:?}", exclusion)),
+        None => Ok(()),
+    }
+}
+
+pub fn load_exclusions() -> Result<Vec<Exclusion>, Box<dyn Error>> {
+    let file = File::open("exclusions.json")?;
+    let exclusions: Vec<Exclusion> = serde_json::from_reader(file)?;
+    validate(&exclusions)?;
+    Ok(exclusions)
+}
+
+/// Rule excluding `token_id` of the `version` collection at `contract`, from a
+/// token rule or, for v2, from a rule on its `slot`.
+pub fn token_rule<'a>(
+    exclusions: &'a [Exclusion],
+    version: &str,
+    contract: &Felt,
+    slot: &Felt,
+    token_id: &Felt,
+) -> Option<&'a Exclusion> {
+    let token_id = token_id.to_bigint().to_string();
+    exclusions.iter().find(|exclusion| match exclusion {
+        Exclusion::Token {
+            version: v,
+            contract: c,
+            token_id: id,
+            ..
+        } => {
+            v.as_deref().is_none_or(|v| v == version)
+                && c.is_none_or(|c| c == *contract)
+                && *id == token_id
+        }
+        Exclusion::Slot {
+            contract: c,
+            slot: s,
+            ..
+        } => version == "v2" && c == contract && *s == slot.to_string(),
+        Exclusion::Owner { .. } => false,
+    })
+}
+
+/// Rule excluding `owner` from the allocation of `project`.
+pub fn owner_rule<'a>(
+    exclusions: &'a [Exclusion],
+    project: &str,
+    owner: &Felt,
+) -> Option<&'a Exclusion> {
+    exclusions.iter().find(|exclusion| match exclusion {
+        Exclusion::Owner {
+            project: p,
+            owner: o,
+            ..
+        } => p.as_deref().is_none_or(|p| p == project) && o == owner,
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules() -> Vec<Exclusion> {
+        serde_json::from_value(json!([
+            { "kind": "token", "version": "v1", "token_id": "2055", "reason": "v1 token", "unconfirmed": true },
+            { "kind": "token", "contract": "0x2", "token_id": "7", "reason": "one contract" },
+            { "kind": "slot", "contract": "0x3", "slot": "2", "reason": "slot" },
+            { "kind": "owner", "project": "Banegas", "owner": "0x10", "reason": "yielder" },
+            { "kind": "owner", "owner": "0x11", "reason": "everywhere" },
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn token_rules_match_version_contract_and_slot() {
+        let rules = rules();
+        let rule = token_rule(&rules, "v1", &Felt::ONE, &Felt::ONE, &Felt::from(2055)).unwrap();
+        assert_eq!(rule.reason(), "v1 token");
+        assert!(rule.unconfirmed());
+        assert!(token_rule(&rules, "v2", &Felt::ONE, &Felt::ONE, &Felt::from(2055)).is_none());
+
+        let seven = Felt::from(7);
+        assert!(token_rule(&rules, "v2", &Felt::TWO, &Felt::ONE, &seven).is_some());
+        assert!(token_rule(&rules, "v2", &Felt::ONE, &Felt::ONE, &seven).is_none());
+
+        let slot = token_rule(&rules, "v2", &Felt::THREE, &Felt::TWO, &Felt::ONE).unwrap();
+        assert!(!slot.unconfirmed());
+        assert!(token_rule(&rules, "v1", &Felt::THREE, &Felt::TWO, &Felt::ONE).is_none());
+        assert!(token_rule(&rules, "v2", &Felt::THREE, &Felt::ONE, &Felt::ONE).is_none());
+    }
+
+    #[test]
+    fn owner_rules_match_their_project() {
+        let rules = rules();
+        let yielder = Felt::from(0x10);
+        assert_eq!(
+            owner_rule(&rules, "Banegas", &yielder).map(Exclusion::reason),
+            Some("yielder")
+        );
+        assert!(owner_rule(&rules, "Delicias", &yielder).is_none());
+        assert!(owner_rule(&rules, "Delicias", &Felt::from(0x11)).is_some());
+    }
+
+    #[test]
+    fn every_rule_needs_a_reason() {
+        let rules: Vec<Exclusion> = serde_json::from_value(json!([
+            { "kind": "owner", "owner": "0x1", "reason": "  " },
+        ]))
+        .unwrap();
+        assert!(validate(&rules).is_err());
+        assert!(validate(&self::rules()).is_ok());
+    }
+}
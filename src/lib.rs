@@ -0,0 +1,16 @@
+//! Helpers shared by the binaries in `src/bin`.
+
+pub mod allocation;
+pub mod bindings;
+pub mod contracts;
+pub mod decode;
+pub mod entrypoints;
+pub mod erc3525;
+pub mod erc721;
+pub mod events;
+pub mod exclusions;
+pub mod merkle;
+pub mod migration;
+pub mod model;
+pub mod rpc;
+pub mod store;
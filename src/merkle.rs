@@ -0,0 +1,129 @@
+//! Merkle tree over a project allocation, for claim contracts that verify with
+//! OpenZeppelin Cairo's `openzeppelin_merkle_tree::merkle_proof::verify_pedersen`.
+//!
+//! That verifier folds the leaf with each proof element through
+//! `PedersenCHasher::commutative_hash`, i.e. `pedersen(min(a, b), max(a, b))`, so
+//! proofs carry no left/right directions. Leaves are `pedersen(owner, amount)`
+//! with the amount in tonnes (9 decimals), which the claim contract recomputes
+//! from the caller and the claimed amount. A node without a sibling is promoted
+//! to the next layer unchanged, and its proof simply has no element for that layer.
+
+use starknet::core::{crypto::pedersen_hash, types::Felt};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default)]
+pub struct MerkleTree {
+    leaves: BTreeMap<Felt, usize>,
+    layers: Vec<Vec<Felt>>,
+}
+
+pub fn leaf(owner: &Felt, amount: u128) -> Felt {
+    pedersen_hash(owner, &Felt::from(amount))
+}
+
+/// `PedersenCHasher::commutative_hash`
+pub fn hash_pair(a: &Felt, b: &Felt) -> Felt {
+    if a < b {
+        pedersen_hash(a, b)
+    } else {
+        pedersen_hash(b, a)
+    }
+}
+
+/// Same fold as `verify_pedersen`.
+pub fn verify(proof: &[Felt], root: &Felt, leaf: &Felt) -> bool {
+    proof
+        .iter()
+        .fold(*leaf, |node, sibling| hash_pair(&node, sibling))
+        == *root
+}
+
+impl MerkleTree {
+    pub fn new(allocation: &BTreeMap<Felt, u128>) -> Self {
+        let mut leaves: Vec<Felt> = allocation
+            .iter()
+            .map(|(owner, amount)| leaf(owner, *amount))
+            .collect();
+        leaves.sort();
+
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            layers.push(next);
+        }
+
+        let leaves = layers[0].iter().enumerate().map(|(i, l)| (*l, i)).collect();
+        MerkleTree { leaves, layers }
+    }
+
+    pub fn root(&self) -> Felt {
+        self.layers
+            .last()
+            .and_then(|layer| layer.first().cloned())
+            .unwrap_or(Felt::ZERO)
+    }
+
+    pub fn proof(&self, owner: &Felt, amount: u128) -> Option<Vec<Felt>> {
+        let mut index = *self.leaves.get(&leaf(owner, amount))?;
+        let mut proof = vec![];
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocation(count: u64) -> BTreeMap<Felt, u128> {
+        (1..=count)
+            .map(|i| (Felt::from(0x1000 + i), 1_000_000_000 * i as u128))
+            .collect()
+    }
+
+    #[test]
+    fn every_proof_verifies_against_the_root() {
+        // Odd sizes exercise promoted nodes, 1 has an empty proof
+        for count in 1..=9 {
+            let allocation = allocation(count);
+            let tree = MerkleTree::new(&allocation);
+            for (owner, amount) in &allocation {
+                let proof = tree.proof(owner, *amount).unwrap();
+                assert!(verify(&proof, &tree.root(), &leaf(owner, *amount)));
+            }
+        }
+    }
+
+    #[test]
+    fn proofs_do_not_verify_other_amounts() {
+        let allocation = allocation(5);
+        let tree = MerkleTree::new(&allocation);
+        let owner = Felt::from(0x1001);
+        let proof = tree.proof(&owner, 1_000_000_000).unwrap();
+        assert!(!verify(&proof, &tree.root(), &leaf(&owner, 2_000_000_000)));
+        assert!(tree.proof(&owner, 2_000_000_000).is_none());
+    }
+
+    #[test]
+    fn two_leaves_hash_in_ascending_order() {
+        let allocation = allocation(2);
+        let tree = MerkleTree::new(&allocation);
+        let mut leaves: Vec<Felt> = allocation.iter().map(|(o, a)| leaf(o, *a)).collect();
+        leaves.sort();
+        assert_eq!(tree.root(), pedersen_hash(&leaves[0], &leaves[1]));
+    }
+}
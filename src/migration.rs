@@ -0,0 +1,368 @@
+use starknet::{
+    core::{
+        crypto::compute_hash_on_elements,
+        types::{Felt, FunctionCall},
+    },
+    macros::selector,
+};
+use std::path::{Path, PathBuf};
+
+/// v3 entrypoint a batch distributes the allocation with.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entrypoint {
+    Mint,
+    Transfer,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Recipient {
+    pub owner: Felt,
+    pub amount: u128,
+}
+
+/// What every call of a batch is built from, besides its recipient.
+#[derive(Debug, Clone, Copy)]
+pub struct CallTemplate {
+    contract: Felt,
+    token_id: u128,
+    /// Holder of the tokens, set only for `Transfer`
+    from: Option<Felt>,
+}
+
+impl CallTemplate {
+    /// Fails when `Transfer` has no holder to transfer from.
+    pub fn new(
+        contract: Felt,
+        entrypoint: Entrypoint,
+        token_id: u128,
+        from: Option<Felt>,
+    ) -> Result<Self, String> {
+        let from = match (entrypoint, from) {
+            (Entrypoint::Mint, _) => None,
+            (Entrypoint::Transfer, Some(from)) => Some(from),
+            (Entrypoint::Transfer, None) => return Err("transfer needs a holder".to_string()),
+        };
+        Ok(CallTemplate {
+            contract,
+            token_id,
+            from,
+        })
+    }
+
+    pub fn entrypoint(&self) -> Entrypoint {
+        match self.from {
+            Some(_) => Entrypoint::Transfer,
+            None => Entrypoint::Mint,
+        }
+    }
+
+    /// `mint(to, token_id, amount)` or `safe_transfer_from(from, to, token_id, amount, data)`.
+    pub fn call(&self, recipient: &Recipient) -> FunctionCall {
+        // u256 arguments are passed as (low, high)
+        let token_id = [Felt::from(self.token_id), Felt::ZERO];
+        let amount = [Felt::from(recipient.amount), Felt::ZERO];
+        match self.from {
+            None => FunctionCall {
+                contract_address: self.contract,
+                entry_point_selector: selector!("mint"),
+                calldata: [vec![recipient.owner], token_id.to_vec(), amount.to_vec()].concat(),
+            },
+            Some(from) => FunctionCall {
+                contract_address: self.contract,
+                entry_point_selector: selector!("safe_transfer_from"),
+                calldata: [
+                    vec![from, recipient.owner],
+                    token_id.to_vec(),
+                    amount.to_vec(),
+                    vec![Felt::ZERO], // empty data span
+                ]
+                .concat(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Batch {
+    pub project: String,
+    pub index: usize,
+    pub contract: Felt,
+    pub entrypoint: Entrypoint,
+    pub token_id: u128,
+    pub from: Option<Felt>,
+    pub recipients: Vec<Recipient>,
+    pub total_amount: u128,
+    pub checksum: Felt,
+    pub calls: Vec<FunctionCall>,
+}
+
+impl Batch {
+    pub fn new(
+        project: &str,
+        index: usize,
+        template: CallTemplate,
+        recipients: Vec<Recipient>,
+    ) -> Self {
+        let calls: Vec<FunctionCall> = recipients.iter().map(|r| template.call(r)).collect();
+        Batch {
+            project: project.to_string(),
+            index,
+            contract: template.contract,
+            entrypoint: template.entrypoint(),
+            token_id: template.token_id,
+            from: template.from,
+            total_amount: recipients.iter().map(|r| r.amount).sum(),
+            checksum: checksum(&recipients, &calls),
+            recipients,
+            calls,
+        }
+    }
+
+    pub fn template(&self) -> Result<CallTemplate, String> {
+        CallTemplate::new(self.contract, self.entrypoint, self.token_id, self.from)
+    }
+
+    /// Checks that the calls are the ones built from the recipients and that the
+    /// checksum covers both, so an edited batch file is never submitted.
+    pub fn verify(&self) -> Result<(), String> {
+        let template = self
+            .template()
+            .map_err(|e| format!("batch {}: {}", self.index, e))?;
+        let expected: Vec<FunctionCall> =
+            self.recipients.iter().map(|r| template.call(r)).collect();
+        if expected != self.calls {
+            return Err(format!(
+                "batch {} calls do not match its recipients",
+                self.index
+            ));
+        }
+        if checksum(&self.recipients, &self.calls) != self.checksum {
+            return Err(format!("batch {} checksum mismatch", self.index));
+        }
+        Ok(())
+    }
+}
+
+/// Length of the account `__execute__` calldata for `calls`, using the same
+/// layout as the multicall `aggregate`: len, then (to, selector, calldata_len, calldata...).
+pub fn execute_calldata_len(calls: &[FunctionCall]) -> usize {
+    1 + calls.iter().map(|c| 3 + c.calldata.len()).sum::<usize>()
+}
+
+/// Hash of every (owner, amount) followed by the `__execute__` calldata of `calls`.
+pub fn checksum(recipients: &[Recipient], calls: &[FunctionCall]) -> Felt {
+    let mut elements: Vec<Felt> = recipients
+        .iter()
+        .flat_map(|r| vec![r.owner, Felt::from(r.amount)])
+        .collect();
+    elements.push(calls.len().into());
+    for call in calls {
+        elements.push(call.contract_address);
+        elements.push(call.entry_point_selector);
+        elements.push(call.calldata.len().into());
+        elements.extend(&call.calldata);
+    }
+    compute_hash_on_elements(&elements)
+}
+
+/// Splits `recipients` in order so each transaction stays within `max_calls` calls
+/// and `max_calldata` felts of `__execute__` calldata.
+pub fn split(
+    recipients: Vec<Recipient>,
+    template: &CallTemplate,
+    max_calls: usize,
+    max_calldata: usize,
+) -> Vec<Vec<Recipient>> {
+    let mut batches: Vec<Vec<Recipient>> = vec![];
+    let mut current: Vec<Recipient> = vec![];
+    let mut current_calls: Vec<FunctionCall> = vec![];
+    for recipient in recipients {
+        current_calls.push(template.call(&recipient));
+        let over_budget =
+            current_calls.len() > max_calls || execute_calldata_len(&current_calls) > max_calldata;
+        if over_budget && !current.is_empty() {
+            batches.push(std::mem::take(&mut current));
+            current_calls = vec![current_calls.pop().unwrap()];
+        }
+        current.push(recipient);
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+/// `batch_NNNN.json` files of `dir`, in index order.
+pub fn batch_paths(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap().to_string_lossy();
+        if name.starts_with("batch_") && name.ends_with(".json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Reads and verifies every batch of `dir`, in index order.
+pub fn load_batches(dir: &Path) -> Result<Vec<Batch>, Box<dyn std::error::Error>> {
+    let mut batches = vec![];
+    for path in batch_paths(dir)? {
+        let batch: Batch = serde_json::from_reader(std::fs::File::open(&path)?)?;
+        batch
+            .verify()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if batch.index != batches.len() {
+            return Err(format!("{}: expected batch {}", path.display(), batches.len()).into());
+        }
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
+/// Recipient of `token_id` in a v3 `TransferSingle` or `TransferBatch` event, keyed
+/// `[selector, operator, from, to]` with the ids and values as u256 in the data.
+/// `None` when the event moves other tokens.
+pub fn token_recipient(
+    keys: &[Felt],
+    data: &[Felt],
+    token_id: u128,
+) -> Result<Option<Felt>, String> {
+    let (Some(selector), Some(to)) = (keys.first(), keys.get(3)) else {
+        return Err(format!("unexpected transfer keys {:?}", keys));
+    };
+    let ids: &[Felt] = if *selector == selector!("TransferSingle") {
+        data.get(..2).ok_or("unexpected TransferSingle data")?
+    } else if *selector == selector!("TransferBatch") {
+        let len: usize = data
+            .first()
+            .and_then(|len| (*len).try_into().ok())
+            .ok_or("unexpected TransferBatch data")?;
+        data.get(1..1 + 2 * len)
+            .ok_or("unexpected TransferBatch data")?
+    } else {
+        return Err(format!(
+            "not a transfer event: {}",
+            selector.to_hex_string()
+        ));
+    };
+    let id = [Felt::from(token_id), Felt::ZERO];
+    Ok(ids.chunks(2).any(|chunk| chunk == id).then_some(*to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet::macros::felt;
+
+    fn template() -> CallTemplate {
+        CallTemplate::new(felt!("0x123"), Entrypoint::Mint, 1, None).unwrap()
+    }
+
+    fn recipients(count: u64) -> Vec<Recipient> {
+        (1..=count)
+            .map(|i| Recipient {
+                owner: Felt::from(i),
+                amount: 1_000 * i as u128,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_respects_call_and_calldata_budgets() {
+        // mint calldata is 5 felts, so 8 felts per call plus the length
+        let batches = split(recipients(10), &template(), 4, 1000);
+        assert_eq!(
+            batches.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![4, 4, 2]
+        );
+
+        let batches = split(recipients(10), &template(), 100, 1 + 3 * 8);
+        assert_eq!(
+            batches.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![3, 3, 3, 1]
+        );
+        let owners: Vec<Felt> = batches.concat().iter().map(|r| r.owner).collect();
+        assert_eq!(owners, (1..=10u64).map(Felt::from).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn verify_rejects_edited_calls_and_recipients() {
+        let batch = Batch::new("Banegas", 0, template(), recipients(3));
+        assert_eq!(batch.verify(), Ok(()));
+
+        let mut edited = Batch::new("Banegas", 0, template(), recipients(3));
+        edited.calls[1].calldata[0] = felt!("0xbad");
+        assert!(edited.verify().is_err());
+
+        let mut edited = Batch::new("Banegas", 0, template(), recipients(3));
+        edited.recipients[2].amount += 1;
+        assert!(edited.verify().is_err());
+
+        let mut edited = Batch::new("Banegas", 0, template(), recipients(3));
+        edited.checksum = Felt::ZERO;
+        assert!(edited.verify().is_err());
+    }
+
+    #[test]
+    fn checksum_covers_calls() {
+        let recipients = recipients(2);
+        let mint = Batch::new("Banegas", 0, template(), recipients.clone());
+        let transfer = Batch::new(
+            "Banegas",
+            0,
+            CallTemplate::new(
+                felt!("0x123"),
+                Entrypoint::Transfer,
+                1,
+                Some(felt!("0x456")),
+            )
+            .unwrap(),
+            recipients,
+        );
+        assert_ne!(mint.checksum, transfer.checksum);
+        assert!(CallTemplate::new(felt!("0x123"), Entrypoint::Transfer, 1, None).is_err());
+    }
+
+    #[test]
+    fn token_recipient_reads_single_and_batch_transfers() {
+        let keys = |selector: Felt| vec![selector, felt!("0x1"), Felt::ZERO, felt!("0xabc")];
+        let single = keys(selector!("TransferSingle"));
+        assert_eq!(
+            token_recipient(
+                &single,
+                &[felt!("0x1"), Felt::ZERO, felt!("0x5"), Felt::ZERO],
+                1
+            ),
+            Ok(Some(felt!("0xabc")))
+        );
+        assert_eq!(
+            token_recipient(
+                &single,
+                &[felt!("0x2"), Felt::ZERO, felt!("0x5"), Felt::ZERO],
+                1
+            ),
+            Ok(None)
+        );
+        let batch = keys(selector!("TransferBatch"));
+        let data = [
+            felt!("0x2"),
+            felt!("0x2"),
+            Felt::ZERO,
+            felt!("0x1"),
+            Felt::ZERO,
+            felt!("0x2"),
+            felt!("0x5"),
+            Felt::ZERO,
+            felt!("0x6"),
+            Felt::ZERO,
+        ];
+        assert_eq!(token_recipient(&batch, &data, 1), Ok(Some(felt!("0xabc"))));
+        assert_eq!(token_recipient(&batch, &data, 3), Ok(None));
+        assert!(token_recipient(&batch, &[felt!("0x2")], 1).is_err());
+        assert!(token_recipient(&single[..2], &[], 1).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Typed view of the snapshot and allocation files, as the CLI computes them.
+
+use crate::allocation::load_allocation;
+use serde_json::{from_reader, json, Value};
+use starknet::core::types::Felt;
+use std::{collections::BTreeMap, error::Error, fs::File, path::Path};
+
+/// Allocation project, with the snapshot files `tonnes` computes it from.
+/// Manual entries live in `adjustments.json`.
+pub const PROJECTS: [(&str, &[&str]); 4] = [
+    ("Banegas", &["BanegasFarmv1", "BanegasFarmv2"]),
+    ("Delicias", &["LasDeliciasv1", "LasDeliciasv2"]),
+    (
+        "Manjarisoa",
+        &["ManjarisoBronzev1", "ManjarisoSilverv1", "Manjarisoav2"],
+    ),
+    ("Karathuru", &["Karathuruv2"]),
+];
+
+/// USDC value (6 decimals) an owner holds in a snapshot.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ValueInfo {
+    pub value: u64,
+    pub value_offset: u64,
+    pub value_yielder: u64,
+}
+
+/// Manual credit (positive) or debit (negative) of USDC value, kept out of
+/// `snapshot/` so generated files never overwrite it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Adjustment {
+    pub project: String,
+    pub owner: Felt,
+    pub amount: i64,
+    pub author: String,
+    pub date: String,
+    pub justification: String,
+}
+
+/// Reads `adjustments.json`, rejecting entries without an audit trail.
+pub fn load_adjustments() -> Result<Vec<Adjustment>, Box<dyn Error>> {
+    let file = File::open("adjustments.json")?;
+    let adjustments: Vec<Adjustment> = from_reader(file)?;
+    for adjustment in &adjustments {
+        if adjustment.author.trim().is_empty()
+            || adjustment.date.trim().is_empty()
+            || adjustment.justification.trim().is_empty()
+        {
+            return Err(format!("adjustment without an audit trail: {:?}", adjustment).into());
+        }
+    }
+    Ok(adjustments)
+}
+
+/// Contract, named as in [`crate::contracts::CONTRACTS`], a project's absorption is read from.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbsorptionSource {
+    /// Yielder `get_max_absorption`, for its project slot
+    Yielder(String),
+    /// ERC-3525 project `get_final_absorption`, for its only slot
+    Project(String),
+}
+
+/// Registry totals of an allocation project, from `projects.json`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ProjectConfig {
+    pub name: String,
+    /// Total tonnes (9 decimals)
+    pub tonnes: u128,
+    /// Project value in USDC (6 decimals)
+    pub value: u128,
+    pub absorption: AbsorptionSource,
+}
+
+/// Reads `projects.json`, which must configure every project of [`PROJECTS`] once.
+pub fn load_project_config() -> Result<Vec<ProjectConfig>, Box<dyn Error>> {
+    let configs: Vec<ProjectConfig> = read_json("projects.json")?
+        .map(serde_json::from_value)
+        .transpose()?
+        .ok_or("projects.json is missing or empty")?;
+    for (project, _) in PROJECTS {
+        let count = configs.iter().filter(|c| c.name == project).count();
+        if count != 1 {
+            return Err(format!("projects.json configures {} {} times", project, count).into());
+        }
+    }
+    if let Some(config) = configs
+        .iter()
+        .find(|c| !PROJECTS.iter().any(|(project, _)| *project == c.name))
+    {
+        return Err(format!("projects.json: unknown project {}", config.name).into());
+    }
+    Ok(configs)
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct OwnerProject {
+    pub tonnes: u128,
+    pub snapshots: BTreeMap<String, ValueInfo>,
+    pub adjustments: Vec<Adjustment>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct ProjectTotals {
+    pub owners: usize,
+    pub tonnes: u128,
+    pub value: u64,
+    pub value_offset: u64,
+    pub value_yielder: u64,
+    pub adjustments: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub metadata: Value,
+    /// Canonical owner address → project → holdings
+    pub owners: BTreeMap<String, BTreeMap<String, OwnerProject>>,
+    pub projects: BTreeMap<String, ProjectTotals>,
+    /// Project → owner → tonnes (9 decimals)
+    pub allocations: BTreeMap<String, BTreeMap<Felt, u128>>,
+}
+
+/// JSON of `path`, `None` when the file is missing or empty, e.g. the snapshot of a
+/// project with no v1 holders. Errors name the path.
+pub fn read_json(path: impl AsRef<Path>) -> Result<Option<Value>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+    };
+    if contents.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        serde_json::from_slice(&contents).map_err(|e| format!("{}: {}", path.display(), e))?,
+    ))
+}
+
+/// Addresses are keyed by their canonical hex form so lookups ignore padding and case.
+pub fn normalize(address: &str) -> Result<String, String> {
+    Felt::from_hex(address)
+        .map(|a| a.to_hex_string())
+        .map_err(|_| format!("invalid address {}", address))
+}
+
+/// Loads `output/meta.json`, `snapshot/`, `adjustments.json` and `tonnes/`.
+/// An owner listed under several spellings of its address is merged into one,
+/// summing what each spelling holds.
+pub fn load() -> Result<Snapshot, Box<dyn Error>> {
+    let mut snapshot = Snapshot {
+        metadata: read_json("output/meta.json")?.unwrap_or(json!({})),
+        ..Default::default()
+    };
+    let adjustments = load_adjustments()?;
+
+    for (project, snapshots) in PROJECTS {
+        let totals = snapshot.projects.entry(project.to_string()).or_default();
+
+        for name in snapshots {
+            // Empty snapshots, e.g. a project with no v1 holders, are skipped
+            let Some(data) = read_json("snapshot/".to_owned() + name + ".json")? else {
+                continue;
+            };
+            for (owner, info) in data.as_object().ok_or("snapshot should be an object")? {
+                let field = |key: &str| {
+                    info[key].as_u64().ok_or_else(|| {
+                        format!("{} of {} in {} should be an integer", key, owner, name)
+                    })
+                };
+                let value = field("value")?;
+                let value_offset = field("value_offset")?;
+                let value_yielder = field("value_yielder")?;
+                totals.value += value;
+                totals.value_offset += value_offset;
+                totals.value_yielder += value_yielder;
+                let held = snapshot
+                    .owners
+                    .entry(normalize(owner)?)
+                    .or_default()
+                    .entry(project.to_string())
+                    .or_default()
+                    .snapshots
+                    .entry(name.to_string())
+                    .or_default();
+                held.value += value;
+                held.value_offset += value_offset;
+                held.value_yielder += value_yielder;
+            }
+        }
+
+        for adjustment in adjustments.iter().filter(|a| a.project == project) {
+            totals.adjustments += adjustment.amount;
+            snapshot
+                .owners
+                .entry(adjustment.owner.to_hex_string())
+                .or_default()
+                .entry(project.to_string())
+                .or_default()
+                .adjustments
+                .push(adjustment.clone());
+        }
+
+        let allocation = load_allocation(project)?;
+        totals.owners = allocation.len();
+        for (owner, amount) in &allocation {
+            totals.tonnes += amount;
+            snapshot
+                .owners
+                .entry(owner.to_hex_string())
+                .or_default()
+                .entry(project.to_string())
+                .or_default()
+                .tonnes = *amount;
+        }
+        snapshot.allocations.insert(project.to_string(), allocation);
+    }
+
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_json_skips_missing_and_empty_files_and_names_malformed_ones() {
+        let dir = std::env::temp_dir().join(format!("model-read-json-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let empty = dir.join("empty.json");
+        let malformed = dir.join("malformed.json");
+        std::fs::write(&empty, "").unwrap();
+        std::fs::write(&malformed, "{").unwrap();
+
+        assert!(read_json(dir.join("missing.json")).unwrap().is_none());
+        assert!(read_json(&empty).unwrap().is_none());
+        let error = read_json(&malformed).unwrap_err().to_string();
+        assert!(error.contains("malformed.json"), "{}", error);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn projects_json_configures_every_project() {
+        let configs = load_project_config().unwrap();
+        assert_eq!(configs.len(), PROJECTS.len());
+        for config in configs {
+            let (AbsorptionSource::Yielder(name) | AbsorptionSource::Project(name)) =
+                &config.absorption;
+            assert!(crate::contracts::by_name(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn normalize_ignores_padding_and_rejects_invalid_addresses() {
+        assert_eq!(normalize("0x00AB").unwrap(), normalize("0xab").unwrap());
+        assert!(normalize("owner").is_err());
+    }
+}
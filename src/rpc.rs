@@ -0,0 +1,540 @@
+use crate::entrypoints::{classify, CallFailure};
+use async_trait::async_trait;
+use serde::{
+    de::{self, DeserializeOwned},
+    Serialize,
+};
+use serde_json::Value;
+use starknet::{
+    core::{
+        crypto::compute_hash_on_elements,
+        types::{
+            requests::{CallRequest, GetClassHashAtRequest, GetClassRequest},
+            BlockId, ContractClass, EmittedEvent, EventFilter, Felt, FunctionCall,
+        },
+        utils::parse_cairo_short_string,
+    },
+    macros::{felt, selector},
+    providers::{
+        jsonrpc::{
+            HttpTransport, HttpTransportError, JsonRpcClient, JsonRpcError, JsonRpcMethod,
+            JsonRpcResponse, JsonRpcTransport,
+        },
+        Provider, ProviderRequestData,
+    },
+};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::time::sleep;
+
+pub const RATE_LIMIT: Duration = Duration::from_millis(100);
+/// Calls per `aggregate` multicall
+pub const CHUNK_SIZE: usize = 200;
+/// Calls per JSON-RPC batch
+pub const BATCH_SIZE: usize = 100;
+/// Events per `get_events` page
+pub const EVENTS_CHUNK_SIZE: u64 = 1000;
+pub const MULTICALL_CONTRACT: Felt =
+    felt!("0x0038e22d0a15703176262dd457a56e5176d13acdfa206d8d397e405223552c9c");
+
+/// `HttpTransport` that caches `starknet_call` results made at a block number
+/// under `CALL_CACHE_DIR` (default `cache/`), as they can never change.
+/// Calls at a block tag always go to the node.
+pub struct CachedTransport {
+    inner: HttpTransport,
+    dir: PathBuf,
+}
+
+impl CachedTransport {
+    /// Asks the node for its chain id and caches under `<dir>/<chain id>/`, so a devnet
+    /// rehearsal and a mainnet run at the same block never share results.
+    pub async fn new(inner: HttpTransport) -> Result<Self, Box<dyn std::error::Error>> {
+        let chain_id = match inner
+            .send_request::<_, Felt>(JsonRpcMethod::ChainId, [(); 0])
+            .await?
+        {
+            JsonRpcResponse::Success { result, .. } => result,
+            JsonRpcResponse::Error { error, .. } => return Err(error.into()),
+        };
+        let chain =
+            parse_cairo_short_string(&chain_id).unwrap_or_else(|_| chain_id.to_hex_string());
+        let dir = std::env::var("CALL_CACHE_DIR").unwrap_or("cache".to_string());
+        Ok(CachedTransport {
+            inner,
+            dir: PathBuf::from(dir).join(chain),
+        })
+    }
+
+    /// `<dir>/<chain id>/<block>/<hash of contract, selector and calldata>.json`
+    fn path(&self, call: &CallRequest) -> Option<PathBuf> {
+        let BlockId::Number(block) = call.block_id else {
+            return None;
+        };
+        let mut elements = vec![
+            call.request.contract_address,
+            call.request.entry_point_selector,
+        ];
+        elements.extend(&call.request.calldata);
+        let key = compute_hash_on_elements(&elements).to_hex_string();
+        Some(self.dir.join(block.to_string()).join(key + ".json"))
+    }
+
+    fn read(&self, path: &Path) -> Option<Value> {
+        serde_json::from_reader(File::open(path).ok()?).ok()
+    }
+
+    fn write(&self, path: &Path, result: &Value) {
+        // A failed write only costs a refetch on the next run
+        let _ = std::fs::create_dir_all(path.parent().unwrap());
+        let _ = std::fs::write(path, result.to_string());
+    }
+}
+
+fn response_id(response: &JsonRpcResponse<Value>) -> u64 {
+    match response {
+        JsonRpcResponse::Success { id, .. } | JsonRpcResponse::Error { id, .. } => *id,
+    }
+}
+
+#[async_trait]
+impl JsonRpcTransport for CachedTransport {
+    type Error = HttpTransportError;
+
+    async fn send_request<P, R>(
+        &self,
+        method: JsonRpcMethod,
+        params: P,
+    ) -> Result<JsonRpcResponse<R>, Self::Error>
+    where
+        P: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let path = match method {
+            JsonRpcMethod::Call => serde_json::to_value(&params)
+                .ok()
+                .and_then(|params| serde_json::from_value(params).ok())
+                .and_then(|call| self.path(&call)),
+            _ => None,
+        };
+        if let Some(result) = path.as_ref().and_then(|path| self.read(path)) {
+            return Ok(JsonRpcResponse::Success {
+                id: 1,
+                result: serde_json::from_value(result).map_err(HttpTransportError::Json)?,
+            });
+        }
+
+        let response: JsonRpcResponse<Value> = self.inner.send_request(method, params).await?;
+        Ok(match response {
+            JsonRpcResponse::Success { id, result } => {
+                if let Some(path) = path {
+                    self.write(&path, &result);
+                }
+                JsonRpcResponse::Success {
+                    id,
+                    result: serde_json::from_value(result).map_err(HttpTransportError::Json)?,
+                }
+            }
+            JsonRpcResponse::Error { id, error } => JsonRpcResponse::Error { id, error },
+        })
+    }
+
+    async fn send_requests<R>(
+        &self,
+        requests: R,
+    ) -> Result<Vec<JsonRpcResponse<Value>>, Self::Error>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        // Answer cached calls from disk and only send the misses, ids are request positions
+        let mut responses = vec![];
+        let mut misses = vec![];
+        let mut sent = vec![];
+        for (id, request) in requests.as_ref().iter().enumerate() {
+            let path = match request {
+                ProviderRequestData::Call(call) => self.path(call),
+                _ => None,
+            };
+            match path.as_ref().and_then(|path| self.read(path)) {
+                Some(result) => responses.push(JsonRpcResponse::Success {
+                    id: id as u64,
+                    result,
+                }),
+                None => {
+                    misses.push((id as u64, path));
+                    sent.push(request.clone());
+                }
+            }
+        }
+
+        if !sent.is_empty() {
+            for response in self.inner.send_requests(sent).await? {
+                let (id, path) = usize::try_from(response_id(&response))
+                    .ok()
+                    .and_then(|id| misses.get(id))
+                    .ok_or_else(|| {
+                        HttpTransportError::Json(de::Error::custom(format!(
+                            "response id {} out of the {} requests sent",
+                            response_id(&response),
+                            misses.len()
+                        )))
+                    })?;
+                responses.push(match response {
+                    JsonRpcResponse::Success { result, .. } => {
+                        if let Some(path) = path {
+                            self.write(path, &result);
+                        }
+                        JsonRpcResponse::Success { id: *id, result }
+                    }
+                    JsonRpcResponse::Error { error, .. } => {
+                        JsonRpcResponse::Error { id: *id, error }
+                    }
+                });
+            }
+        }
+
+        responses.sort_by_key(response_id);
+        Ok(responses)
+    }
+}
+
+/// Events of `contract` keyed `key`, from genesis to `block_id`.
+pub async fn events_of(
+    provider: &impl Provider,
+    contract: Felt,
+    key: Felt,
+    block_id: BlockId,
+) -> Result<Vec<EmittedEvent>, Box<dyn std::error::Error>> {
+    let filter = EventFilter {
+        from_block: Some(BlockId::Number(0)),
+        to_block: Some(block_id),
+        address: Some(contract),
+        keys: Some(vec![vec![key]]),
+    };
+    let mut events = vec![];
+    let mut continuation_token = None;
+    loop {
+        let page = provider
+            .get_events(filter.clone(), continuation_token, EVENTS_CHUNK_SIZE)
+            .await?;
+        sleep(RATE_LIMIT).await; // Rate limit delay
+        events.extend(page.events);
+
+        continuation_token = page.continuation_token;
+        if continuation_token.is_none() {
+            break;
+        }
+    }
+    Ok(events)
+}
+
+/// Every account that ever deposited in `yielder` up to `block_id`, from the first
+/// field of its `Deposit` events.
+pub async fn yielder_depositors(
+    provider: &impl Provider,
+    yielder: Felt,
+    block_id: BlockId,
+) -> Result<BTreeSet<Felt>, Box<dyn std::error::Error>> {
+    let mut depositors = BTreeSet::new();
+    for event in events_of(provider, yielder, selector!("Deposit"), block_id).await? {
+        let depositor = event.data.first().ok_or(format!(
+            "unexpected Deposit layout in {}",
+            event.transaction_hash.to_hex_string()
+        ))?;
+        depositors.insert(*depositor);
+    }
+    Ok(depositors)
+}
+
+/// How a `Batcher` reaches the node, picked per run with `CALL_STRATEGY`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallStrategy {
+    /// `aggregate` calls to `MULTICALL_CONTRACT`, mainnet only, a single revert fails a chunk
+    Multicall,
+    /// JSON-RPC batches of `starknet_call`, every call succeeds or fails on its own
+    Batch,
+}
+
+impl CallStrategy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "multicall" => Ok(CallStrategy::Multicall),
+            "batch" => Ok(CallStrategy::Batch),
+            other => Err(format!(
+                "unknown CALL_STRATEGY {:?}, expected multicall or batch",
+                other
+            )),
+        }
+    }
+}
+
+/// Sends batches of calls with the strategy of the run.
+pub struct Batcher {
+    transport: CachedTransport,
+    strategy: CallStrategy,
+}
+
+impl Batcher {
+    /// `CALL_STRATEGY=batch` sends JSON-RPC batches, `multicall` or no value uses the
+    /// multicall contract, any other value is rejected.
+    pub fn from_env(transport: CachedTransport) -> Result<Self, Box<dyn std::error::Error>> {
+        let strategy = match std::env::var("CALL_STRATEGY") {
+            Ok(strategy) => CallStrategy::parse(&strategy)?,
+            Err(_) => CallStrategy::Multicall,
+        };
+        println!("Batching calls with {:?}", strategy);
+        Ok(Batcher {
+            transport,
+            strategy,
+        })
+    }
+
+    /// Response to every request in order, in JSON-RPC batches of `BATCH_SIZE`.
+    async fn try_requests<T: DeserializeOwned>(
+        &self,
+        requests: &[ProviderRequestData],
+    ) -> Result<Vec<Result<T, JsonRpcError>>, Box<dyn std::error::Error>> {
+        let mut results = vec![];
+        for chunk in requests.chunks(BATCH_SIZE) {
+            let responses = self.transport.send_requests(chunk).await?;
+            sleep(RATE_LIMIT).await; // Rate limit delay
+
+            if responses.len() != chunk.len() {
+                return Err(format!(
+                    "sent {} requests in a batch but got {} responses",
+                    chunk.len(),
+                    responses.len()
+                )
+                .into());
+            }
+            for response in responses {
+                results.push(match response {
+                    JsonRpcResponse::Success { result, .. } => Ok(serde_json::from_value(result)?),
+                    JsonRpcResponse::Error { error, .. } => Err(error),
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Result of every call in order, in JSON-RPC batches of `BATCH_SIZE` whatever the
+    /// strategy, so one failing call does not fail the others.
+    pub async fn try_calls(
+        &self,
+        calls: &[FunctionCall],
+        block_id: BlockId,
+    ) -> Result<Vec<Result<Vec<Felt>, JsonRpcError>>, Box<dyn std::error::Error>> {
+        let requests: Vec<ProviderRequestData> = calls
+            .iter()
+            .map(|call| {
+                ProviderRequestData::Call(CallRequest {
+                    request: call.clone(),
+                    block_id,
+                })
+            })
+            .collect();
+        self.try_requests(&requests).await
+    }
+
+    /// Class hash of every contract in order, in JSON-RPC batches of `BATCH_SIZE`.
+    pub async fn try_class_hashes(
+        &self,
+        contracts: &[Felt],
+        block_id: BlockId,
+    ) -> Result<Vec<Result<Felt, JsonRpcError>>, Box<dyn std::error::Error>> {
+        let requests: Vec<ProviderRequestData> = contracts
+            .iter()
+            .map(|contract| {
+                ProviderRequestData::GetClassHashAt(GetClassHashAtRequest {
+                    block_id,
+                    contract_address: *contract,
+                })
+            })
+            .collect();
+        self.try_requests(&requests).await
+    }
+
+    /// Class of every class hash in order, in JSON-RPC batches of `BATCH_SIZE`.
+    pub async fn try_classes(
+        &self,
+        class_hashes: &[Felt],
+        block_id: BlockId,
+    ) -> Result<Vec<Result<ContractClass, JsonRpcError>>, Box<dyn std::error::Error>> {
+        let requests: Vec<ProviderRequestData> = class_hashes
+            .iter()
+            .map(|class_hash| {
+                ProviderRequestData::GetClass(GetClassRequest {
+                    block_id,
+                    class_hash: *class_hash,
+                })
+            })
+            .collect();
+        self.try_requests(&requests).await
+    }
+
+    /// Result of every call in order, failing if any call fails.
+    pub async fn aggregate(
+        &self,
+        provider: &JsonRpcClient<CachedTransport>,
+        calls: &[FunctionCall],
+        block_id: BlockId,
+    ) -> Result<Vec<Vec<Felt>>, Box<dyn std::error::Error>> {
+        match self.strategy {
+            CallStrategy::Multicall => multicall(provider, calls, block_id).await,
+            CallStrategy::Batch => {
+                let mut results = vec![];
+                for (call, result) in calls.iter().zip(self.try_calls(calls, block_id).await?) {
+                    results.push(result.map_err(|error| {
+                        format!(
+                            "call to {} on {} failed: {}",
+                            call.entry_point_selector.to_hex_string(),
+                            call.contract_address.to_hex_string(),
+                            error.message
+                        )
+                    })?);
+                }
+                Ok(results)
+            }
+        }
+    }
+
+    /// Outcome of every call in order, so one failing call does not fail the others.
+    /// A reverted multicall chunk is retried as a JSON-RPC batch, then every call that still
+    /// fails is probed on its own to classify why. A probe the node does not answer is
+    /// recorded as `Unanswered` rather than failing the other calls.
+    pub async fn try_aggregate(
+        &self,
+        provider: &JsonRpcClient<CachedTransport>,
+        calls: &[FunctionCall],
+        block_id: BlockId,
+    ) -> Result<Vec<Result<Vec<Felt>, CallFailure>>, Box<dyn std::error::Error>> {
+        if self.strategy == CallStrategy::Batch {
+            return self.retry_calls(provider, calls, block_id).await;
+        }
+
+        // A reverted chunk is retried on its own, the others keep their results
+        let mut results = vec![];
+        for chunk in calls.chunks(CHUNK_SIZE) {
+            match multicall_chunk(provider, chunk, block_id).await {
+                Ok(chunk_results) => results.extend(chunk_results.into_iter().map(Ok)),
+                Err(e) => {
+                    println!(
+                        "Multicall failed ({}), retrying its {} calls one by one",
+                        e,
+                        chunk.len()
+                    );
+                    results.extend(self.retry_calls(provider, chunk, block_id).await?);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Outcome of each of `calls` from a JSON-RPC batch, probing every call that
+    /// failed in it alone to classify the failure.
+    async fn retry_calls(
+        &self,
+        provider: &JsonRpcClient<CachedTransport>,
+        calls: &[FunctionCall],
+        block_id: BlockId,
+    ) -> Result<Vec<Result<Vec<Felt>, CallFailure>>, Box<dyn std::error::Error>> {
+        let mut results = vec![];
+        for (call, result) in calls.iter().zip(self.try_calls(calls, block_id).await?) {
+            if let Ok(data) = result {
+                results.push(Ok(data));
+                continue;
+            }
+            let outcome = match provider.call(call, block_id).await {
+                Ok(data) => Ok(data),
+                Err(error) => Err(classify(&error).unwrap_or_else(|| {
+                    println!(
+                        "No answer for {} on {}: {}",
+                        call.entry_point_selector.to_hex_string(),
+                        call.contract_address.to_hex_string(),
+                        error
+                    );
+                    CallFailure::Unanswered
+                })),
+            };
+            sleep(RATE_LIMIT).await; // Rate limit delay
+            results.push(outcome);
+        }
+        Ok(results)
+    }
+}
+
+/// Runs `calls` through the multicall contract in chunks of `CHUNK_SIZE`,
+/// returning the result of each call in order.
+async fn multicall(
+    provider: &JsonRpcClient<CachedTransport>,
+    calls: &[FunctionCall],
+    block_id: BlockId,
+) -> Result<Vec<Vec<Felt>>, Box<dyn std::error::Error>> {
+    let mut results = vec![];
+    for chunk in calls.chunks(CHUNK_SIZE) {
+        results.extend(multicall_chunk(provider, chunk, block_id).await?);
+    }
+    Ok(results)
+}
+
+/// Runs one chunk of calls in a single `aggregate` call.
+async fn multicall_chunk(
+    provider: &JsonRpcClient<CachedTransport>,
+    chunk: &[FunctionCall],
+    block_id: BlockId,
+) -> Result<Vec<Vec<Felt>>, Box<dyn std::error::Error>> {
+    let mut calldata = vec![chunk.len().into()];
+    for call in chunk {
+        calldata.push(call.contract_address);
+        calldata.push(call.entry_point_selector);
+        calldata.push(call.calldata.len().into());
+        calldata.extend(call.calldata.clone());
+    }
+    let res = provider
+        .call(
+            FunctionCall {
+                contract_address: MULTICALL_CONTRACT,
+                entry_point_selector: selector!("aggregate"),
+                calldata,
+            },
+            block_id,
+        )
+        .await?;
+    sleep(RATE_LIMIT).await; // Rate limit delay
+
+    // [block_number, results_len, (len, data...)...]
+    let mut results = vec![];
+    let mut data = res.iter().skip(2);
+    while let Some(len) = data.next() {
+        let len: usize = len.to_bigint().to_string().parse()?;
+        results.push(data.by_ref().take(len).cloned().collect());
+    }
+    if results.len() != chunk.len() {
+        return Err(format!(
+            "multicall returned {} results for {} calls",
+            results.len(),
+            chunk.len()
+        )
+        .into());
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_call_strategies_are_rejected() {
+        assert_eq!(CallStrategy::parse("batch"), Ok(CallStrategy::Batch));
+        assert_eq!(
+            CallStrategy::parse("multicall"),
+            Ok(CallStrategy::Multicall)
+        );
+        assert!(CallStrategy::parse("batches").is_err());
+        assert!(CallStrategy::parse("").is_err());
+    }
+}
@@ -0,0 +1,393 @@
+//! SQLite history of snapshot runs, written by `scan` and `total` and queried by `store`.
+
+use crate::model::{normalize, read_json};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::Value;
+use std::{collections::BTreeMap, error::Error, path::Path, time::SystemTime};
+
+/// Database `scan --record` and `total --record` write to, unless `SNAPSHOT_DB` is set.
+pub const DEFAULT_DB: &str = "snapshots.db";
+
+/// A run is identified by its block and scan configuration, so recording it again
+/// replaces its rows instead of adding a second run.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    block INTEGER NOT NULL,
+    block_hash TEXT,
+    timestamp INTEGER NOT NULL,
+    config_hash TEXT,
+    recorded_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tokens (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    project TEXT NOT NULL,
+    version TEXT NOT NULL,
+    slot TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    value TEXT NOT NULL,
+    value_in_yielder TEXT NOT NULL,
+    value_in_offsetter TEXT NOT NULL,
+    account TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS owners (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    snapshot TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    value TEXT NOT NULL,
+    value_offset TEXT NOT NULL,
+    value_yielder TEXT NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS runs_key ON runs(block, IFNULL(config_hash, ''));
+CREATE UNIQUE INDEX IF NOT EXISTS tokens_key ON tokens(run_id, project, version, slot, token_id);
+CREATE UNIQUE INDEX IF NOT EXISTS owners_key ON owners(run_id, snapshot, owner);
+CREATE INDEX IF NOT EXISTS tokens_run ON tokens(run_id, project, version, token_id);
+CREATE INDEX IF NOT EXISTS owners_owner ON owners(owner);
+";
+
+#[derive(Debug, Clone)]
+pub struct RunInfo {
+    pub block: u64,
+    pub block_hash: Option<String>,
+    pub timestamp: u64,
+    pub config_hash: Option<String>,
+}
+
+impl RunInfo {
+    /// Run described by `output/meta.json`.
+    pub fn from_meta() -> Result<Self, Box<dyn Error>> {
+        let meta = read_json("output/meta.json")?
+            .ok_or("output/meta.json should exist, use `import` for older snapshots")?;
+        Ok(RunInfo {
+            block: meta["block_number"]
+                .as_u64()
+                .ok_or("output/meta.json has no block_number")?,
+            block_hash: meta["block_hash"].as_str().map(String::from),
+            timestamp: meta["timestamp"]
+                .as_u64()
+                .ok_or("output/meta.json has no timestamp")?,
+            config_hash: meta["config_hash"].as_str().map(String::from),
+        })
+    }
+}
+
+/// Token present in at most one of two runs, or with a different owner in each.
+#[derive(Debug, PartialEq)]
+pub struct TokenChange {
+    pub project: String,
+    pub version: String,
+    pub token_id: String,
+    /// `None` when the token was added in the later run
+    pub old_owner: Option<String>,
+    /// `None` when the token is gone from the later run
+    pub new_owner: Option<String>,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+pub fn open(path: &str) -> Result<Connection, Box<dyn Error>> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+/// Records the current files with `record` when `SNAPSHOT_DB` is set or `--record`
+/// was passed, into `SNAPSHOT_DB` or `DEFAULT_DB`. Skipped with a warning when
+/// `output/meta.json`, which identifies the run, is missing.
+pub fn record_if_requested(
+    record: impl FnOnce(&mut Connection, &RunInfo) -> Result<i64, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let db = std::env::var("SNAPSHOT_DB").ok();
+    if db.is_none() && !std::env::args().any(|arg| arg == "--record") {
+        return Ok(());
+    }
+    if !Path::new("output/meta.json").exists() {
+        eprintln!("Warning: output/meta.json is missing, the run is not recorded");
+        return Ok(());
+    }
+    record(
+        &mut open(db.as_deref().unwrap_or(DEFAULT_DB))?,
+        &RunInfo::from_meta()?,
+    )?;
+    Ok(())
+}
+
+/// Id of `run`, inserting it the first time it is recorded.
+pub fn run_id(tx: &Transaction, run: &RunInfo) -> Result<i64, Box<dyn Error>> {
+    tx.execute(
+        "INSERT INTO runs (block, block_hash, timestamp, config_hash, recorded_at)
+         VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT DO NOTHING",
+        params![
+            run.block,
+            run.block_hash,
+            run.timestamp,
+            run.config_hash,
+            now()
+        ],
+    )?;
+    let id = tx
+        .query_row(
+            "SELECT id FROM runs WHERE block = ?1 AND IFNULL(config_hash, '') = IFNULL(?2, '')",
+            params![run.block, run.config_hash],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(id.ok_or("run should exist after insert")?)
+}
+
+/// Replaces the tokens of `run_id` in `version` with those of a scan output.
+pub fn insert_tokens(
+    tx: &Transaction,
+    run_id: i64,
+    version: &str,
+    data: &Value,
+) -> Result<usize, Box<dyn Error>> {
+    tx.execute(
+        "DELETE FROM tokens WHERE run_id = ?1 AND version = ?2",
+        params![run_id, version],
+    )?;
+    let mut count = 0;
+    for (project, slots) in data.as_object().ok_or("scan output should be an object")? {
+        for (slot, tokens) in slots.as_object().ok_or("slot should be an object")? {
+            for (token_id, info) in tokens.as_object().ok_or("tokens should be an object")? {
+                let field = |key: &str| info[key].as_str().unwrap_or_default().to_string();
+                tx.execute(
+                    "INSERT INTO tokens VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        run_id,
+                        project,
+                        version,
+                        slot,
+                        token_id,
+                        normalize(&field("owner"))?,
+                        field("value"),
+                        field("value_in_yielder"),
+                        field("value_in_offsetter"),
+                        field("account"),
+                    ],
+                )?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// Replaces the owner aggregates of `run_id` in `snapshot`. An owner listed under
+/// several spellings of its address is stored once, with the sum of its values.
+pub fn insert_owners(
+    tx: &Transaction,
+    run_id: i64,
+    snapshot: &str,
+    data: &Value,
+) -> Result<usize, Box<dyn Error>> {
+    tx.execute(
+        "DELETE FROM owners WHERE run_id = ?1 AND snapshot = ?2",
+        params![run_id, snapshot],
+    )?;
+    let mut owners: BTreeMap<String, [u128; 3]> = BTreeMap::new();
+    for (owner, info) in data.as_object().ok_or("snapshot should be an object")? {
+        let values = owners.entry(normalize(owner)?).or_default();
+        for (total, key) in values
+            .iter_mut()
+            .zip(["value", "value_offset", "value_yielder"])
+        {
+            *total += u128::from(
+                info[key]
+                    .as_u64()
+                    .ok_or_else(|| format!("{} of {} should be an integer", key, owner))?,
+            );
+        }
+    }
+    // Values are stored as text, SQLite integers do not hold a u128
+    for (owner, [value, value_offset, value_yielder]) in &owners {
+        tx.execute(
+            "INSERT INTO owners VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                run_id,
+                snapshot,
+                owner,
+                value.to_string(),
+                value_offset.to_string(),
+                value_yielder.to_string(),
+            ],
+        )?;
+    }
+    Ok(owners.len())
+}
+
+/// Records the tokens of `output/sv1.json` and `output/sv2.json` under `run`.
+pub fn record_tokens(conn: &mut Connection, run: &RunInfo) -> Result<i64, Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let run_id = run_id(&tx, run)?;
+    let mut count = 0;
+    for version in ["v1", "v2"] {
+        if let Some(data) = read_json("output/s".to_owned() + version + ".json")? {
+            count += insert_tokens(&tx, run_id, version, &data)?;
+        }
+    }
+    tx.commit()?;
+    println!(
+        "Recorded {} tokens as run {} (block {})",
+        count, run_id, run.block
+    );
+    Ok(run_id)
+}
+
+/// Records the owner aggregates of `snapshot/` under `run`.
+pub fn record_owners(conn: &mut Connection, run: &RunInfo) -> Result<i64, Box<dyn Error>> {
+    let tx = conn.transaction()?;
+    let run_id = run_id(&tx, run)?;
+    let mut count = 0;
+    for entry in std::fs::read_dir("snapshot")? {
+        let path = entry?.path();
+        // Empty snapshots, e.g. a project with no v1 holders, are skipped
+        let Some(data) = read_json(&path)? else {
+            continue;
+        };
+        let snapshot = path.file_stem().unwrap().to_string_lossy().to_string();
+        count += insert_owners(&tx, run_id, &snapshot, &data)?;
+    }
+    tx.commit()?;
+    println!(
+        "Recorded {} owner aggregates as run {} (block {})",
+        count, run_id, run.block
+    );
+    Ok(run_id)
+}
+
+/// Tokens added, removed or transferred between runs `from` and `to`.
+pub fn token_changes(
+    conn: &Connection,
+    from: i64,
+    to: i64,
+) -> Result<Vec<TokenChange>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(a.project, b.project), COALESCE(a.version, b.version),
+                COALESCE(a.token_id, b.token_id), a.owner, b.owner
+         FROM (SELECT * FROM tokens WHERE run_id = ?1) a
+         FULL OUTER JOIN (SELECT * FROM tokens WHERE run_id = ?2) b
+           ON a.project = b.project AND a.version = b.version
+          AND a.slot = b.slot AND a.token_id = b.token_id
+         WHERE a.owner IS NULL OR b.owner IS NULL OR a.owner != b.owner
+         ORDER BY 1, 2, CAST(COALESCE(a.token_id, b.token_id) AS INTEGER)",
+    )?;
+    let rows = stmt.query_map([from, to], |row| {
+        Ok(TokenChange {
+            project: row.get(0)?,
+            version: row.get(1)?,
+            token_id: row.get(2)?,
+            old_owner: row.get(3)?,
+            new_owner: row.get(4)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run() -> RunInfo {
+        RunInfo {
+            block: 900000,
+            block_hash: None,
+            timestamp: 1,
+            config_hash: None,
+        }
+    }
+
+    fn tokens(owners: &[(&str, &str)]) -> Value {
+        let tokens: serde_json::Map<String, Value> = owners
+            .iter()
+            .map(|(id, owner)| (id.to_string(), json!({ "owner": owner, "value": "1" })))
+            .collect();
+        json!({ "Banegas": { "1": tokens } })
+    }
+
+    #[test]
+    fn recording_a_run_twice_keeps_one_run() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        for _ in 0..2 {
+            let tx = conn.transaction().unwrap();
+            let id = run_id(&tx, &run()).unwrap();
+            insert_tokens(&tx, id, "v2", &tokens(&[("1", "0x1")])).unwrap();
+            let snapshot = json!({
+                "0x02ab": { "value": 1, "value_offset": 0, "value_yielder": 0 },
+                "0x2ab": { "value": 2, "value_offset": 0, "value_yielder": 0 },
+            });
+            insert_owners(&tx, id, "BanegasFarmv2", &snapshot).unwrap();
+            tx.commit().unwrap();
+        }
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0))
+                .unwrap()
+        };
+        assert_eq!(count("runs"), 1);
+        assert_eq!(count("tokens"), 1);
+        assert_eq!(count("owners"), 1);
+        let value: String = conn
+            .query_row("SELECT value FROM owners", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(value, "3");
+    }
+
+    #[test]
+    fn changes_include_added_and_removed_tokens() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        let tx = conn.transaction().unwrap();
+        let first = run_id(&tx, &run()).unwrap();
+        insert_tokens(
+            &tx,
+            first,
+            "v2",
+            &tokens(&[("1", "0x1"), ("2", "0x2"), ("3", "0x3")]),
+        )
+        .unwrap();
+        let second = run_id(
+            &tx,
+            &RunInfo {
+                block: 900001,
+                ..run()
+            },
+        )
+        .unwrap();
+        insert_tokens(
+            &tx,
+            second,
+            "v2",
+            &tokens(&[("1", "0x1"), ("2", "0x5"), ("4", "0x4")]),
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        let changes = token_changes(&conn, first, second).unwrap();
+        let summary: Vec<(&str, Option<&str>, Option<&str>)> = changes
+            .iter()
+            .map(|c| {
+                (
+                    c.token_id.as_str(),
+                    c.old_owner.as_deref(),
+                    c.new_owner.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2", Some("0x2"), Some("0x5")),
+                ("3", Some("0x3"), None),
+                ("4", None, Some("0x4")),
+            ]
+        );
+    }
+}